@@ -0,0 +1,80 @@
+//! Timings for tokenizing and reading large inputs.
+//!
+//! Run with `cargo bench`. Each case is timed at several input sizes, doubling
+//! each time, and fails if the time per byte grows with the input, as it would
+//! if the work were quadratic.
+use std::{hint::black_box, time::Instant};
+
+use mal::{parser::tokenize, reader::read_str};
+
+/// The input sizes to time each case at, in bytes.
+const SIZES: [usize; 4] = [250_000, 500_000, 1_000_000, 2_000_000];
+
+/// How many times slower per byte the largest input may be than the smallest
+/// before the case is considered to scale badly. Linear work stays near 1,
+/// while quadratic work would be about 8.
+const MAX_SLOWDOWN: f64 = 4.0;
+
+/// Time `run` on the input built by `make_input` at each of [SIZES], keeping
+/// the best of a few runs to reduce noise.
+fn bench(name: &str, make_input: impl Fn(usize) -> String, run: impl Fn(&str)) {
+    let mut per_byte = vec![];
+    for size in SIZES {
+        let input = make_input(size);
+        let best = (0..5)
+            .map(|_| {
+                let start = Instant::now();
+                run(black_box(&input));
+                start.elapsed()
+            })
+            .min()
+            .unwrap();
+        let ns = best.as_nanos() as f64 / input.len() as f64;
+        println!(
+            "{name:<24} {:>9} bytes {best:>12.2?} {ns:>8.2} ns/byte",
+            input.len()
+        );
+        per_byte.push(ns);
+    }
+    let slowdown = per_byte[per_byte.len() - 1] / per_byte[0];
+    assert!(
+        slowdown < MAX_SLOWDOWN,
+        "{name} is {slowdown:.1}x slower per byte on the largest input"
+    );
+}
+
+fn main() {
+    bench(
+        "tokenize long symbol",
+        |size| format!("({})", "a".repeat(size)),
+        |input| {
+            black_box(tokenize(input).unwrap());
+        },
+    );
+    bench(
+        "tokenize long string",
+        |size| format!("\"{}\"", "a".repeat(size)),
+        |input| {
+            black_box(tokenize(input).unwrap());
+        },
+    );
+    bench(
+        "read string literals",
+        |size| {
+            format!(
+                "[{}]",
+                format!("\"{}\" ", "x".repeat(98)).repeat(size / 100)
+            )
+        },
+        |input| {
+            black_box(read_str(input).unwrap());
+        },
+    );
+    bench(
+        "read mixed literals",
+        |size| format!("[{}]", "(1 :key \"str\" 2.5 sym) ".repeat(size / 25)),
+        |input| {
+            black_box(read_str(input).unwrap());
+        },
+    );
+}
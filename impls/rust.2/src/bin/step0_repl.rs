@@ -1,8 +1,6 @@
 use std::error::Error;
 
-use rustyline::{error::ReadlineError, Editor};
-
-const HISTFILE: &str = ".mal_history";
+use mal::repl::{self, Options};
 
 fn read(input: &str) -> &str {
     input
@@ -21,27 +19,5 @@ fn rep(input: &str) -> &str {
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let mut editor = Editor::<()>::new()?;
-    editor.load_history(HISTFILE).ok();
-
-    loop {
-        match editor.readline("user> ") {
-            Ok(input) => {
-                let input = input.trim();
-                editor.add_history_entry(input);
-                if !input.is_empty() {
-                    println!("{}", rep(input));
-                }
-            }
-            Err(ReadlineError::Interrupted) => continue,
-            Err(ReadlineError::Eof) => break,
-            Err(e) => {
-                eprintln!("error: {e}");
-                continue;
-            }
-        }
-    }
-
-    editor.save_history(HISTFILE)?;
-    Ok(())
+    repl::run(Options::default(), |input| vec![Ok(rep(input).to_owned())])
 }
@@ -1,14 +1,13 @@
 use std::error::Error;
 
-use rustyline::{error::ReadlineError, Editor};
-
 use mal::{
     printer::pr_str,
     reader::{read_str, ReadError},
+    repl::{self, Options},
     types::Value,
+    MalError,
 };
 
-const HISTFILE: &str = ".mal_history";
 const PRETTYPRINT: bool = false;
 const DBGINFO: bool = false;
 
@@ -30,7 +29,7 @@ fn print(input: Value) -> String {
     if DBGINFO {
         println!("print: {input:?}");
     }
-    pr_str(input, PRETTYPRINT)
+    pr_str(&input, PRETTYPRINT)
 }
 
 fn rep(input: &str) -> Result<String, ReadError> {
@@ -38,31 +37,7 @@ fn rep(input: &str) -> Result<String, ReadError> {
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let mut editor = Editor::<()>::new()?;
-    editor.load_history(HISTFILE).ok();
-
-    loop {
-        match editor.readline("user> ") {
-            Ok(input) => {
-                let input = input.trim();
-                editor.add_history_entry(input);
-                if !input.is_empty() {
-                    match rep(input) {
-                        Ok(output) => println!("{output}"),
-                        Err(ReadError::NoInput) => (),
-                        Err(error) => eprintln!("error: {error}"),
-                    }
-                }
-            }
-            Err(ReadlineError::Interrupted) => continue,
-            Err(ReadlineError::Eof) => break,
-            Err(e) => {
-                eprintln!("error: {e}");
-                continue;
-            }
-        }
-    }
-
-    editor.save_history(HISTFILE)?;
-    Ok(())
+    repl::run(Options::default(), |input| {
+        vec![rep(input).map_err(MalError::from)]
+    })
 }
@@ -0,0 +1,131 @@
+use std::{
+    env,
+    error::Error,
+    io::{self, IsTerminal, Read},
+    panic, process, thread,
+};
+
+use mal::{
+    completion::MalHelper,
+    core::default_env,
+    env::Env,
+    eval::{eval, set_max_depth, set_warn_on_shadow, DEFAULT_MAX_DEPTH},
+    printer::{pr_str_styled, ColorMode},
+    reader::{read_str_all, ReadError},
+    rep_all_styled, rep_styled,
+    repl::{self, Options},
+    MalError,
+};
+
+/// The stack size of the thread running the interpreter, enough for
+/// [DEFAULT_MAX_DEPTH] nested evaluations even in a debug build.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+const USAGE: &str =
+    "usage: stepA_mal [--color auto|always|never] [--no-warn-shadow] [-e|--eval EXPR]...";
+
+struct Args {
+    /// The expressions given with `-e`/`--eval`, in order.
+    exprs: Vec<String>,
+    color: ColorMode,
+    /// Whether `def!` warns when it rebinds a builtin.
+    warn_on_shadow: bool,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args {
+        exprs: vec![],
+        color: ColorMode::Auto,
+        warn_on_shadow: true,
+    };
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-e" | "--eval" => match args.next() {
+                Some(expr) => parsed.exprs.push(expr),
+                None => return Err(format!("{arg} requires an expression")),
+            },
+            "--color" => match args.next() {
+                Some(mode) => parsed.color = mode.parse()?,
+                None => return Err(format!("{arg} requires a mode")),
+            },
+            "--no-warn-shadow" => parsed.warn_on_shadow = false,
+            _ => return Err(format!("unexpected argument: {arg}")),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Evaluate every form piped in on stdin, printing the result of each.
+fn run_stdin(env: &Env, color: bool) -> Result<(), Box<dyn Error>> {
+    let mut source = String::new();
+    io::stdin().read_to_string(&mut source)?;
+
+    let forms = read_str_all(&source).unwrap_or_else(|error| {
+        eprintln!("error: {error}");
+        process::exit(1);
+    });
+    for form in forms {
+        match eval(form, env) {
+            Ok(value) => println!("{}", pr_str_styled(&value, false, color)),
+            Err(error) => {
+                eprintln!("error: {error}");
+                process::exit(1);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Resolve the recursion limit from the value of `MAL_MAX_DEPTH`, falling back
+/// to [DEFAULT_MAX_DEPTH] if it is unset or not a number.
+fn max_depth(max_depth: Option<String>) -> usize {
+    max_depth
+        .and_then(|depth| depth.trim().parse().ok())
+        .unwrap_or(DEFAULT_MAX_DEPTH)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    // Run on a thread with a stack large enough for the recursion limit, so
+    // that deep recursion fails with a mal error instead of a stack overflow.
+    let interpreter = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(|| run().map_err(|error| error.to_string()))?;
+    match interpreter.join() {
+        Ok(result) => Ok(result?),
+        Err(payload) => panic::resume_unwind(payload),
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let args = parse_args(env::args().skip(1)).unwrap_or_else(|error| {
+        eprintln!("error: {error}\n{USAGE}");
+        process::exit(2);
+    });
+
+    set_warn_on_shadow(args.warn_on_shadow);
+    set_max_depth(max_depth(env::var("MAL_MAX_DEPTH").ok()));
+    let env = default_env();
+    let color = args.color.enabled();
+
+    if !args.exprs.is_empty() {
+        for expr in args.exprs {
+            match rep_styled(&expr, &env, color) {
+                Ok(output) => println!("{output}"),
+                Err(MalError::Read(ReadError::NoInput)) => (),
+                Err(error) => {
+                    eprintln!("error: {error}");
+                    process::exit(1);
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    if !io::stdin().is_terminal() {
+        return run_stdin(&env, color);
+    }
+
+    let options = Options::new(Some(MalHelper::new(&env)));
+    repl::run(options, |input| rep_all_styled(input, &env, color))
+}
@@ -0,0 +1,99 @@
+//! Tab completion of symbol names for the REPL.
+use rustyline::{
+    completion::Completer, highlight::Highlighter, hint::Hinter, validate::Validator, Context,
+    Helper,
+};
+
+use crate::{env::Env, eval::SPECIAL_FORMS};
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || "()[]{}'`~@^\",;".contains(c)
+}
+
+/// Find the byte position where the word ending at `pos` in `line` starts.
+fn word_start(line: &str, pos: usize) -> usize {
+    line[..pos]
+        .char_indices()
+        .rev()
+        .find(|(_, c)| is_delimiter(*c))
+        .map_or(0, |(i, c)| i + c.len_utf8())
+}
+
+/// Select the symbols from `symbols` that start with `prefix`.
+///
+/// An empty prefix matches nothing, so that pressing tab on blank input does
+/// not list every known symbol.
+pub fn candidates(prefix: &str, symbols: &[String]) -> Vec<String> {
+    if prefix.is_empty() {
+        return vec![];
+    }
+    symbols
+        .iter()
+        .filter(|symbol| symbol.starts_with(prefix))
+        .cloned()
+        .collect()
+}
+
+/// A rustyline helper that completes symbols bound in an environment, as well
+/// as the names of special forms.
+pub struct MalHelper {
+    env: Env,
+}
+
+impl MalHelper {
+    /// Create a helper completing symbols from `env` and its outer
+    /// environments.
+    pub fn new(env: &Env) -> Self {
+        Self { env: env.clone() }
+    }
+}
+
+impl Completer for MalHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = word_start(line, pos);
+        let mut symbols = self.env.symbols();
+        symbols.extend(SPECIAL_FORMS.iter().map(|form| form.to_string()));
+        symbols.sort();
+        symbols.dedup();
+        Ok((start, candidates(&line[start..pos], &symbols)))
+    }
+}
+
+impl Hinter for MalHelper {
+    type Hint = String;
+}
+
+impl Highlighter for MalHelper {}
+
+impl Validator for MalHelper {}
+
+impl Helper for MalHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::{candidates, word_start};
+
+    #[test]
+    fn test_candidates() {
+        let symbols = ["def!", "defmacro!", "deref", "do", "list"].map(String::from);
+        assert_eq!(candidates("def", &symbols), vec!["def!", "defmacro!"]);
+        assert_eq!(candidates("d", &symbols).len(), 4);
+        assert_eq!(candidates("x", &symbols), Vec::<String>::new());
+        assert_eq!(candidates("", &symbols), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_word_start() {
+        assert_eq!(word_start("(li", 3), 1);
+        assert_eq!(word_start("(+ 1 (cou", 9), 6);
+        assert_eq!(word_start("list", 2), 0);
+        assert_eq!(word_start("(a ", 3), 3);
+    }
+}
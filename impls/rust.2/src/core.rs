@@ -0,0 +1,2368 @@
+//! Built-in functions available to every mal program.
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    cmp::Ordering,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    env::Env,
+    eval::{apply, check_arity, eval, expect_sequence, expect_symbol, set_tracing, EvalError},
+    map::Map,
+    printer::pr_str,
+    reader::{read_str, read_str_all, ReadError},
+    symbol::Symbol,
+    types::{is_truthy, Atom, Function, NativeFn, Value},
+};
+
+/// Definitions written in mal itself, evaluated when the default environment
+/// is created.
+const BOOTSTRAP: &str = r#"
+(defmacro! or
+  (fn* (& xs)
+    (if (empty? xs)
+      nil
+      (if (= 1 (count xs))
+        (first xs)
+        (let* (condvar (gensym))
+          (list 'let* (list condvar (first xs))
+            (list 'if condvar condvar (cons 'or (rest xs)))))))))
+
+(defmacro! and
+  (fn* (& xs)
+    (if (empty? xs)
+      true
+      (if (= 1 (count xs))
+        (first xs)
+        (let* (condvar (gensym))
+          (list 'let* (list condvar (first xs))
+            (list 'if condvar (cons 'and (rest xs)) condvar)))))))
+"#;
+
+fn expect_int(value: &Value) -> Result<i64, EvalError> {
+    match value {
+        Value::Atom(Atom::Int(int)) => Ok(*int),
+        other => Err(EvalError::TypeMismatch {
+            expected: "int",
+            got: other.type_name(),
+        }),
+    }
+}
+
+fn expect_string(value: &Value) -> Result<&str, EvalError> {
+    match value {
+        Value::Atom(Atom::String(string)) => Ok(string),
+        other => Err(EvalError::TypeMismatch {
+            expected: "string",
+            got: other.type_name(),
+        }),
+    }
+}
+
+/// Returns `value` as a hash-map key. As when reading a hash-map literal, only
+/// atoms can be keys.
+fn expect_key(value: &Value) -> Result<Atom, EvalError> {
+    match value {
+        Value::Atom(atom) => Ok(atom.clone()),
+        other => Err(EvalError::UnhashableType(other.clone())),
+    }
+}
+
+fn expect_map(value: &Value) -> Result<&Map, EvalError> {
+    match value {
+        Value::HashMap(map) => Ok(map),
+        other => Err(EvalError::TypeMismatch {
+            expected: "hashmap",
+            got: other.type_name(),
+        }),
+    }
+}
+
+/// Insert each key and value pair from `args` into `map`.
+fn insert_pairs(map: &mut Map, args: &[Value]) -> Result<(), EvalError> {
+    if !args.len().is_multiple_of(2) {
+        return Err(EvalError::UnevenKeyValues);
+    }
+    for pair in args.chunks(2) {
+        map.insert(expect_key(&pair[0])?, pair[1].clone());
+    }
+    Ok(())
+}
+
+/// Like [expect_sequence], but nil is treated as an empty sequence.
+fn expect_sequence_or_nil(value: &Value) -> Result<&[Value], EvalError> {
+    match value {
+        Value::Atom(Atom::Nil) => Ok(&[]),
+        other => expect_sequence(other),
+    }
+}
+
+/// Like [expect_sequence_or_nil], but a string is also treated as a sequence
+/// of its characters.
+///
+/// This allocates a value for every character of a string, so builtins that
+/// only look at part of one should handle strings themselves.
+fn expect_items(value: &Value) -> Result<Cow<'_, [Value]>, EvalError> {
+    match value {
+        Value::Atom(Atom::String(string)) => Ok(Cow::Owned(
+            string.chars().map(|c| Value::Atom(Atom::Char(c))).collect(),
+        )),
+        other => expect_sequence_or_nil(other).map(Cow::Borrowed),
+    }
+}
+
+fn expect_float(value: &Value) -> Result<f64, EvalError> {
+    match value {
+        Value::Atom(Atom::Int(int)) => Ok(*int as f64),
+        Value::Atom(Atom::Float(float)) => Ok(float.0),
+        other => Err(EvalError::TypeMismatch {
+            expected: "number",
+            got: other.type_name(),
+        }),
+    }
+}
+
+/// The arguments to an arithmetic builtin, all promoted to floats if any of
+/// them is a float.
+enum Numbers {
+    Ints(Vec<i64>),
+    Floats(Vec<f64>),
+}
+
+fn expect_numbers(args: &[Value]) -> Result<Numbers, EvalError> {
+    if args
+        .iter()
+        .any(|arg| matches!(arg, Value::Atom(Atom::Float(_))))
+    {
+        args.iter()
+            .map(expect_float)
+            .collect::<Result<_, _>>()
+            .map(Numbers::Floats)
+    } else {
+        args.iter()
+            .map(expect_int)
+            .collect::<Result<_, _>>()
+            .map(Numbers::Ints)
+    }
+}
+
+fn values_equal(a: &Value, b: &Value) -> bool {
+    if let (Some(a), Some(b)) = (a.as_seq(), b.as_seq()) {
+        return a.len() == b.len() && a.iter().zip(b).all(|(a, b)| values_equal(a, b));
+    }
+    match (a, b) {
+        (Value::Pair(a), Value::Pair(b)) => values_equal(&a.0, &b.0) && values_equal(&a.1, &b.1),
+        (Value::HashMap(a), Value::HashMap(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(k, v)| b.get(k).is_some_and(|other| values_equal(v, other)))
+        }
+        (a, b) => a == b,
+    }
+}
+
+fn join_printed(args: &[Value], pretty: bool, separator: &str) -> String {
+    args.iter()
+        .map(|value| pr_str(value, pretty))
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+fn add(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    match expect_numbers(args)? {
+        Numbers::Ints(ints) => ints
+            .into_iter()
+            .try_fold(0, i64::checked_add)
+            .map(Value::from)
+            .ok_or(EvalError::IntegerOverflow),
+        Numbers::Floats(floats) => Ok(Value::from(floats.into_iter().sum::<f64>())),
+    }
+}
+
+fn subtract(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, usize::MAX)?;
+    match expect_numbers(args)? {
+        Numbers::Ints(ints) => match ints.as_slice() {
+            [int] => int.checked_neg(),
+            [first, rest @ ..] => rest
+                .iter()
+                .try_fold(*first, |acc, int| acc.checked_sub(*int)),
+            [] => Some(0),
+        }
+        .map(Value::from)
+        .ok_or(EvalError::IntegerOverflow),
+        Numbers::Floats(floats) => Ok(Value::from(match floats.as_slice() {
+            [float] => -float,
+            [first, rest @ ..] => rest.iter().fold(*first, |acc, float| acc - float),
+            [] => 0.0,
+        })),
+    }
+}
+
+fn multiply(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    match expect_numbers(args)? {
+        Numbers::Ints(ints) => ints
+            .into_iter()
+            .try_fold(1, i64::checked_mul)
+            .map(Value::from)
+            .ok_or(EvalError::IntegerOverflow),
+        Numbers::Floats(floats) => Ok(Value::from(floats.into_iter().product::<f64>())),
+    }
+}
+
+/// Integer division truncates and errors on a zero divisor. Float division
+/// follows IEEE 754, so dividing by zero gives an infinity or `NaN`.
+fn divide(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 2, usize::MAX)?;
+    match expect_numbers(args)? {
+        Numbers::Ints(ints) => {
+            let mut result = ints[0];
+            for int in &ints[1..] {
+                if *int == 0 {
+                    return Err(EvalError::DivisionByZero);
+                }
+                result = result.checked_div(*int).ok_or(EvalError::IntegerOverflow)?;
+            }
+            Ok(Value::from(result))
+        }
+        Numbers::Floats(floats) => Ok(Value::from(
+            floats[1..].iter().fold(floats[0], |acc, float| acc / float),
+        )),
+    }
+}
+
+fn compare(args: &[Value], op: fn(Ordering) -> bool) -> Result<Value, EvalError> {
+    check_arity(args, 2, 2)?;
+    let ordering = match expect_numbers(args)? {
+        Numbers::Ints(ints) => Some(ints[0].cmp(&ints[1])),
+        Numbers::Floats(floats) => floats[0].partial_cmp(&floats[1]),
+    };
+    Ok(Value::from(ordering.is_some_and(op)))
+}
+
+fn less_than(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    compare(args, Ordering::is_lt)
+}
+
+fn less_than_or_equal(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    compare(args, Ordering::is_le)
+}
+
+fn greater_than(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    compare(args, Ordering::is_gt)
+}
+
+fn greater_than_or_equal(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    compare(args, Ordering::is_ge)
+}
+
+fn equal(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 2, 2)?;
+    Ok(Value::from(values_equal(&args[0], &args[1])))
+}
+
+fn not(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    Ok(Value::from(!is_truthy(&args[0])))
+}
+
+fn list(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    Ok(Value::list(args.to_vec()))
+}
+
+fn is_list(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    Ok(Value::from(matches!(args[0], Value::List(_))))
+}
+
+/// `(symbol? x)`: whether `x` is a symbol. Strings that look like symbols are
+/// not symbols.
+fn is_symbol(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    Ok(Value::from(matches!(args[0], Value::Atom(Atom::Symbol(_)))))
+}
+
+/// `(nil? x)`: whether `x` is nil.
+fn is_nil(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    Ok(Value::from(matches!(args[0], Value::Atom(Atom::Nil))))
+}
+
+/// `(true? x)`: whether `x` is `true` itself, rather than merely truthy.
+fn is_true(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    Ok(Value::from(matches!(args[0], Value::Atom(Atom::True))))
+}
+
+/// `(false? x)`: whether `x` is `false` itself. Nil is falsey but not `false`.
+fn is_false(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    Ok(Value::from(matches!(args[0], Value::Atom(Atom::False))))
+}
+
+/// `(some? x)`: whether `x` is anything but nil. Unlike truthiness, this is
+/// true for `false`.
+fn is_some(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    Ok(Value::from(!matches!(args[0], Value::Atom(Atom::Nil))))
+}
+
+/// Returns `value` as the name of a new symbol or keyword. Names that are
+/// empty or only whitespace are rejected, since they would not read back in.
+fn expect_name(value: &Value) -> Result<&str, EvalError> {
+    let name = expect_string(value)?;
+    if name.trim().is_empty() {
+        return Err(EvalError::TypeMismatch {
+            expected: "non-blank string",
+            got: "blank string".to_owned(),
+        });
+    }
+    Ok(name)
+}
+
+/// `(symbol name)`: the symbol called `name`.
+fn symbol(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    Ok(Value::Atom(Atom::Symbol(Symbol::new(expect_name(
+        &args[0],
+    )?))))
+}
+
+/// `(keyword name)`: the keyword called `name`, without a leading colon. A
+/// keyword is returned unchanged.
+fn keyword(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    match &args[0] {
+        Value::Atom(Atom::Keyword(_)) => Ok(args[0].clone()),
+        other => Ok(Value::Atom(Atom::Keyword(expect_name(other)?.to_owned()))),
+    }
+}
+
+/// `(keyword? x)`: whether `x` is a keyword.
+fn is_keyword(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    Ok(Value::from(matches!(
+        args[0],
+        Value::Atom(Atom::Keyword(_))
+    )))
+}
+
+/// `(fn? x)`: whether `x` is a function that can be called, meaning a
+/// built-in function or a closure that is not a macro.
+fn is_fn(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    Ok(Value::from(match &args[0] {
+        Value::Function(Function::Native { .. }) => true,
+        Value::Function(Function::Closure(closure)) => !closure.is_macro,
+        _ => false,
+    }))
+}
+
+/// `(macro? x)`: whether `x` is a macro defined with `defmacro!`.
+fn is_macro(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    Ok(Value::from(matches!(
+        &args[0],
+        Value::Function(Function::Closure(closure)) if closure.is_macro
+    )))
+}
+
+/// `(empty? coll)`: whether a list, vector, or string has no items. Nil is
+/// empty. This reads the length directly, so it is cheap on large collections.
+fn is_empty(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    let empty = match &args[0] {
+        Value::Atom(Atom::String(string)) => string.is_empty(),
+        other => expect_sequence_or_nil(other)?.is_empty(),
+    };
+    Ok(Value::from(empty))
+}
+
+/// `(count coll)`: the number of items in a list or vector, entries in a
+/// hash-map, or characters in a string. Nil counts as empty.
+fn count(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    let count = match &args[0] {
+        Value::Atom(Atom::Nil) => 0,
+        Value::Atom(Atom::String(string)) => string.chars().count(),
+        Value::List(items) | Value::Vector(items) => items.len(),
+        Value::HashMap(map) => map.len(),
+        other => {
+            return Err(EvalError::TypeMismatch {
+                expected: "collection",
+                got: other.type_name(),
+            })
+        }
+    };
+    Ok(Value::Atom(Atom::Int(count as i64)))
+}
+
+/// `(cons x coll)`: a list of `x` followed by the items of `coll`.
+///
+/// Like `conj` and `concat`, this always builds a new sequence with no
+/// metadata, and leaves its arguments unchanged.
+fn cons(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 2, 2)?;
+    let mut items = vec![args[0].clone()];
+    items.extend_from_slice(expect_sequence(&args[1])?);
+    Ok(Value::list(items))
+}
+
+/// `(conj coll x...)`: `coll` with each `x` added where it is cheapest, so
+/// items are prepended to a list, in turn, and appended to a vector.
+fn conj(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, usize::MAX)?;
+    let additions = &args[1..];
+    match &args[0] {
+        Value::List(items) => Ok(Value::list(
+            additions
+                .iter()
+                .rev()
+                .chain(items.iter())
+                .cloned()
+                .collect(),
+        )),
+        Value::Vector(items) => Ok(Value::vector(
+            items.iter().chain(additions).cloned().collect(),
+        )),
+        other => Err(EvalError::TypeMismatch {
+            expected: "sequence",
+            got: other.type_name(),
+        }),
+    }
+}
+
+/// `(vec coll)`: a vector of the items of the list or vector `coll`. A vector
+/// is returned unchanged.
+fn vec(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    match &args[0] {
+        Value::Vector(_) => Ok(args[0].clone()),
+        other => Ok(Value::vector(expect_sequence(other)?.to_vec())),
+    }
+}
+
+/// `(into to from)`: `to` with the items of `from` added as by `conj`, so
+/// pouring into a list reverses the items. Pouring into a map takes each item
+/// of `from` as a `[key value]` pair. A map given as `from` yields its entries
+/// as such pairs.
+fn into(args: &[Value], env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 2, 2)?;
+    let from = match &args[1] {
+        Value::HashMap(map) => Cow::Owned(
+            map.iter()
+                .map(|(k, v)| Value::vector(vec![Value::Atom(k.clone()), v.clone()]))
+                .collect(),
+        ),
+        other => Cow::Borrowed(expect_sequence_or_nil(other)?),
+    };
+    match &args[0] {
+        Value::HashMap(map) => {
+            let mut map = map.clone();
+            for entry in from.iter() {
+                let Some([k, v]) = entry.as_seq() else {
+                    return Err(EvalError::TypeMismatch {
+                        expected: "key/value pair",
+                        got: entry.type_name(),
+                    });
+                };
+                map.insert(expect_key(k)?, v.clone());
+            }
+            Ok(Value::HashMap(map))
+        }
+        to => {
+            let mut conj_args = vec![to.clone()];
+            conj_args.extend(from.iter().cloned());
+            conj(&conj_args, env)
+        }
+    }
+}
+
+/// `(concat coll...)`: a list of the items of each `coll` in turn. Nil is
+/// treated as an empty sequence.
+fn concat(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    let mut items = vec![];
+    for coll in args {
+        items.extend_from_slice(expect_sequence_or_nil(coll)?);
+    }
+    Ok(Value::list(items))
+}
+
+/// `(first coll)`: the first item of a list or vector, or nil if `coll` is
+/// empty or nil.
+///
+/// Like `rest`, `nth`, and `count`, this treats a string as a sequence of
+/// characters, so `(first "abc")` is the char `\a`.
+fn first(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    let first = match &args[0] {
+        Value::Atom(Atom::String(string)) => {
+            string.chars().next().map(|c| Value::Atom(Atom::Char(c)))
+        }
+        other => expect_sequence_or_nil(other)?.first().cloned(),
+    };
+    Ok(first.unwrap_or(Value::Atom(Atom::Nil)))
+}
+
+/// `(rest coll)`: every item of a list or vector but the first. The result is
+/// always a list, even when `coll` is a vector or string, and is empty if
+/// `coll` is empty or nil.
+fn rest(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    let items = expect_items(&args[0])?;
+    Ok(Value::list(items.get(1..).unwrap_or_default().to_vec()))
+}
+
+/// `(nth coll index)` or `(nth coll index default)`: the item at `index` in a
+/// list, vector, or string, counting from zero. Strings are indexed by
+/// character rather than by byte.
+///
+/// An index that is negative or past the end gives `default` if there is one,
+/// and is an error rather than nil otherwise.
+fn nth(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 2, 3)?;
+    let index = expect_int(&args[1])?;
+    let i = usize::try_from(index).ok();
+    let (item, len) = match &args[0] {
+        Value::Atom(Atom::String(string)) => match i.and_then(|i| string.chars().nth(i)) {
+            Some(c) => return Ok(Value::Atom(Atom::Char(c))),
+            None => (None, string.chars().count()),
+        },
+        other => {
+            let items = expect_sequence_or_nil(other)?;
+            (i.and_then(|i| items.get(i)).cloned(), items.len())
+        }
+    };
+    item.or_else(|| args.get(2).cloned())
+        .ok_or(EvalError::IndexOutOfRange { index, len })
+}
+
+/// Clamp a `take`/`drop` count to `0..=len`.
+fn clamp_count(n: i64, len: usize) -> usize {
+    usize::try_from(n).unwrap_or(0).min(len)
+}
+
+/// `(take n coll)`: a list of the first `n` items of `coll`. A count past the
+/// end takes everything and a negative count takes nothing.
+fn take(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 2, 2)?;
+    let n = expect_int(&args[0])?;
+    let items = expect_items(&args[1])?;
+    Ok(Value::list(items[..clamp_count(n, items.len())].to_vec()))
+}
+
+/// `(drop n coll)`: a list of every item of `coll` after the first `n`. A
+/// count past the end leaves an empty list and a negative count drops nothing.
+fn drop(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 2, 2)?;
+    let n = expect_int(&args[0])?;
+    let items = expect_items(&args[1])?;
+    Ok(Value::list(items[clamp_count(n, items.len())..].to_vec()))
+}
+
+/// `(range end)`, `(range start end)`, or `(range start end step)`: a list of
+/// the integers from `start` (default 0) up to but not including `end`,
+/// counting by `step` (default 1). A negative step counts down towards `end`.
+fn range(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 3)?;
+    let ints = args.iter().map(expect_int).collect::<Result<Vec<_>, _>>()?;
+    let (start, end, step) = match ints[..] {
+        [end] => (0, end, 1),
+        [start, end] => (start, end, 1),
+        [start, end, step] => (start, end, step),
+        _ => unreachable!("arity is checked"),
+    };
+    if step == 0 {
+        return Err(EvalError::ZeroStep);
+    }
+    let mut items = vec![];
+    let mut n = Some(start);
+    while let Some(i) = n.filter(|&i| if step > 0 { i < end } else { i > end }) {
+        items.push(Value::from(i));
+        n = i.checked_add(step);
+    }
+    Ok(Value::list(items))
+}
+
+/// `(apply f arg... coll)`: call `f` with the given args followed by the items
+/// of `coll`, which must be a list or vector.
+fn apply_(args: &[Value], env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 2, usize::MAX)?;
+    let (last, middle) = args[1..].split_last().expect("arity is checked");
+    let rest = last.as_seq().ok_or_else(|| EvalError::TypeMismatch {
+        expected: "list or vector as last argument",
+        got: last.type_name(),
+    })?;
+    let mut call_args = middle.to_vec();
+    call_args.extend_from_slice(rest);
+    apply(&args[0], call_args, env)
+}
+
+/// `(map f coll...)`: a list of the results of calling `f` on each item of
+/// `coll`. Given several collections, `f` is called with one item from each,
+/// stopping at the end of the shortest.
+fn map(args: &[Value], env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 2, usize::MAX)?;
+    let colls = args[1..]
+        .iter()
+        .map(expect_sequence)
+        .collect::<Result<Vec<_>, _>>()?;
+    let len = colls.iter().map(|coll| coll.len()).min().unwrap_or(0);
+    (0..len)
+        .map(|i| {
+            let call_args = colls.iter().map(|coll| coll[i].clone()).collect();
+            apply(&args[0], call_args, env)
+        })
+        .collect::<Result<_, _>>()
+        .map(Value::list)
+}
+
+/// Sort `items` by the key at the same index in `keys`, keeping items with
+/// equal keys in their original order.
+///
+/// The keys must be all numbers or all strings. Numbers compare as they do
+/// with `<`, so ints and floats can be mixed, and strings compare by
+/// character. Any other mix of types is an error.
+fn sort_by_keys(items: &[Value], keys: &[Value]) -> Result<Value, EvalError> {
+    let mut order: Vec<usize> = (0..items.len()).collect();
+    match keys.first() {
+        Some(Value::Atom(Atom::String(_))) => {
+            let strings = keys
+                .iter()
+                .map(expect_string)
+                .collect::<Result<Vec<_>, _>>()?;
+            order.sort_by_key(|&i| strings[i]);
+        }
+        _ => match expect_numbers(keys)? {
+            Numbers::Ints(ints) => order.sort_by_key(|&i| ints[i]),
+            Numbers::Floats(floats) => order.sort_by(|&a, &b| floats[a].total_cmp(&floats[b])),
+        },
+    }
+    Ok(Value::list(
+        order.into_iter().map(|i| items[i].clone()).collect(),
+    ))
+}
+
+/// `(sort coll)`: a list of the items of `coll` in ascending order. See
+/// [sort_by_keys] for which items can be compared.
+fn sort(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    let items = expect_sequence_or_nil(&args[0])?;
+    sort_by_keys(items, items)
+}
+
+/// `(sort-by f coll)`: a list of the items of `coll` in ascending order of
+/// `(f item)`. Items with equal keys keep their original order.
+fn sort_by(args: &[Value], env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 2, 2)?;
+    let items = expect_sequence_or_nil(&args[1])?;
+    let keys = items
+        .iter()
+        .map(|item| apply(&args[0], vec![item.clone()], env))
+        .collect::<Result<Vec<_>, _>>()?;
+    sort_by_keys(items, &keys)
+}
+
+/// Collect the items of `coll` for which the truthiness of `(pred item)` is
+/// `keep`.
+fn select(args: &[Value], env: &Env, keep: bool) -> Result<Value, EvalError> {
+    check_arity(args, 2, 2)?;
+    let mut result = vec![];
+    for item in expect_sequence(&args[1])? {
+        if is_truthy(&apply(&args[0], vec![item.clone()], env)?) == keep {
+            result.push(item.clone());
+        }
+    }
+    Ok(Value::list(result))
+}
+
+/// `(filter pred coll)`: a list of the items of `coll` for which `pred` returns
+/// a truthy value.
+fn filter(args: &[Value], env: &Env) -> Result<Value, EvalError> {
+    select(args, env, true)
+}
+
+/// `(remove pred coll)`: a list of the items of `coll` for which `pred`
+/// returns a falsey value.
+fn remove(args: &[Value], env: &Env) -> Result<Value, EvalError> {
+    select(args, env, false)
+}
+
+/// `(reduce f init coll)` or `(reduce f coll)`: fold `coll` from the left,
+/// calling `f` with the accumulated value and each item in turn.
+///
+/// Without `init` the first item of `coll` is used as the seed, which is an
+/// error if `coll` is empty.
+fn reduce(args: &[Value], env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 2, 3)?;
+    let (init, items) = match args {
+        [_, init, coll] => (Some(init.clone()), expect_sequence(coll)?),
+        [_, coll] => (None, expect_sequence(coll)?),
+        _ => unreachable!("arity was checked above"),
+    };
+    let (init, items) = match init {
+        Some(init) => (init, items),
+        None => match items.split_first() {
+            Some((first, rest)) => (first.clone(), rest),
+            None => return Err(EvalError::EmptyReduce),
+        },
+    };
+    items.iter().try_fold(init, |acc, item| {
+        apply(&args[0], vec![acc, item.clone()], env)
+    })
+}
+
+/// `(atom value)`: a new reference holding `value`.
+fn atom(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    Ok(Value::reference(args[0].clone()))
+}
+
+fn is_atom(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    Ok(Value::from(matches!(args[0], Value::Ref(_))))
+}
+
+fn expect_ref(value: &Value) -> Result<&Rc<RefCell<Value>>, EvalError> {
+    match value {
+        Value::Ref(reference) => Ok(reference),
+        other => Err(EvalError::TypeMismatch {
+            expected: "ref",
+            got: other.type_name(),
+        }),
+    }
+}
+
+/// `(deref ref)`: the value currently held by `ref`.
+fn deref(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    Ok(expect_ref(&args[0])?.borrow().clone())
+}
+
+/// `(reset! ref value)`: replace the value held by `ref` with `value`, and
+/// return it.
+fn reset(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 2, 2)?;
+    *expect_ref(&args[0])?.borrow_mut() = args[1].clone();
+    Ok(args[1].clone())
+}
+
+/// `(swap! ref f args...)`: replace the value held by `ref` with the result of
+/// `(f value args...)`, and return it.
+///
+/// No borrow of `ref` is held while `f` runs, so `f` may itself read or reset
+/// `ref`. Its result still replaces whatever `ref` holds when it returns.
+fn swap(args: &[Value], env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 2, usize::MAX)?;
+    let reference = expect_ref(&args[0])?;
+    let current = reference.borrow().clone();
+    let mut call_args = vec![current];
+    call_args.extend_from_slice(&args[2..]);
+    let value = apply(&args[1], call_args, env)?;
+    *reference.borrow_mut() = value.clone();
+    Ok(value)
+}
+
+/// `(time-ms)`: milliseconds since the Unix epoch.
+fn time_ms(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 0, 0)?;
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(Value::Atom(Atom::Int(elapsed.as_millis() as i64)))
+}
+
+/// `(sleep ms)`: block for `ms` milliseconds and return nil.
+fn sleep(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    let ms = u64::try_from(expect_int(&args[0])?).map_err(|_| EvalError::TypeMismatch {
+        expected: "non-negative int",
+        got: "negative int".to_owned(),
+    })?;
+    thread::sleep(Duration::from_millis(ms));
+    Ok(Value::Atom(Atom::Nil))
+}
+
+fn throw(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    Err(EvalError::Thrown(args[0].clone()))
+}
+
+/// `(trace enabled)`: print each form to stderr before it is evaluated while
+/// `enabled` is truthy.
+fn trace(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    set_tracing(is_truthy(&args[0]));
+    Ok(Value::Atom(Atom::Nil))
+}
+
+/// `(meta value)`: the metadata attached to `value`, or nil.
+fn meta(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    Ok(args[0].meta())
+}
+
+/// `(with-meta value meta)`: a copy of the collection or function `value` with
+/// `meta` attached.
+fn with_meta(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 2, 2)?;
+    args[0].with_meta(args[1].clone())
+}
+
+fn gensym(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 0, 0)?;
+    Ok(Value::Atom(Atom::Symbol(Symbol::gensym())))
+}
+
+fn hash_map(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    let mut map = Map::new();
+    insert_pairs(&mut map, args)?;
+    Ok(Value::HashMap(map))
+}
+
+fn assoc(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, usize::MAX)?;
+    let mut map = expect_map(&args[0])?.clone();
+    insert_pairs(&mut map, &args[1..])?;
+    Ok(Value::HashMap(map))
+}
+
+/// `(dissoc map key...)`: a copy of `map` without the given keys. The other
+/// entries keep their order.
+fn dissoc(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, usize::MAX)?;
+    let mut map = expect_map(&args[0])?.clone();
+    for key in &args[1..] {
+        map.remove(&expect_key(key)?);
+    }
+    Ok(Value::HashMap(map))
+}
+
+/// `(get map key)` or `(get map key default)`: the value for `key` in `map`,
+/// or `default` (nil if not given) if it is missing or `map` is nil.
+///
+/// Keys only match when they are the same kind of atom, so `:a` and `"a"` are
+/// distinct keys.
+fn get(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 2, 3)?;
+    let value = match &args[0] {
+        Value::Atom(Atom::Nil) => None,
+        other => expect_map(other)?.get(&expect_key(&args[1])?),
+    };
+    Ok(value
+        .or(args.get(2))
+        .cloned()
+        .unwrap_or(Value::Atom(Atom::Nil)))
+}
+
+/// `(get-in coll path)` or `(get-in coll path default)`: the value found by
+/// looking up each key of `path` in turn, starting from `coll`, or `default`
+/// (nil if not given) if any step misses.
+///
+/// Maps are looked up by key as with [get], and lists and vectors by integer
+/// index. Reaching nil, a missing key, or an index out of range is a miss.
+fn get_in(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 2, 3)?;
+    let mut value = Some(&args[0]);
+    for key in expect_sequence(&args[1])? {
+        value = match value {
+            None | Some(Value::Atom(Atom::Nil)) => None,
+            Some(Value::HashMap(map)) => map.get(&expect_key(key)?),
+            Some(other) => {
+                let index = expect_int(key)?;
+                let items = expect_sequence(other)?;
+                usize::try_from(index).ok().and_then(|i| items.get(i))
+            }
+        };
+    }
+    Ok(value
+        .or(args.get(2))
+        .cloned()
+        .unwrap_or(Value::Atom(Atom::Nil)))
+}
+
+/// A copy of `coll` with the value at `path` replaced by `value`, looking up
+/// each step as [get_in] does. A step that is nil or missing becomes a new
+/// map, and an empty path gives `value` itself.
+fn assoc_path(coll: &Value, path: &[Value], value: Value) -> Result<Value, EvalError> {
+    let Some((key, rest)) = path.split_first() else {
+        return Ok(value);
+    };
+    match coll {
+        Value::Atom(Atom::Nil) => assoc_path(&Value::HashMap(Map::new()), path, value),
+        Value::Vector(items) => {
+            let index = expect_int(key)?;
+            let i = usize::try_from(index)
+                .ok()
+                .filter(|&i| i < items.len())
+                .ok_or(EvalError::IndexOutOfRange {
+                    index,
+                    len: items.len(),
+                })?;
+            let mut items = items.to_vec();
+            items[i] = assoc_path(&items[i], rest, value)?;
+            Ok(Value::vector(items))
+        }
+        other => {
+            let mut map = expect_map(other)?.clone();
+            let key = expect_key(key)?;
+            let inner = map.get(&key).cloned().unwrap_or(Value::Atom(Atom::Nil));
+            map.insert(key, assoc_path(&inner, rest, value)?);
+            Ok(Value::HashMap(map))
+        }
+    }
+}
+
+/// `(assoc-in coll path value)`: a copy of `coll` with `value` stored at the
+/// nested `path`. Missing maps along the way are created, and `coll` itself is
+/// left unchanged.
+fn assoc_in(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 3, 3)?;
+    assoc_path(&args[0], expect_sequence(&args[1])?, args[2].clone())
+}
+
+/// `(update map key f arg...)`: a copy of `map` with the value for `key`
+/// replaced by `(f value arg...)`. A missing key passes nil to `f`.
+fn update(args: &[Value], env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 3, usize::MAX)?;
+    let mut map = match &args[0] {
+        Value::Atom(Atom::Nil) => Map::new(),
+        other => expect_map(other)?.clone(),
+    };
+    let key = expect_key(&args[1])?;
+    let mut call_args = vec![map.get(&key).cloned().unwrap_or(Value::Atom(Atom::Nil))];
+    call_args.extend_from_slice(&args[3..]);
+    map.insert(key, apply(&args[2], call_args, env)?);
+    Ok(Value::HashMap(map))
+}
+
+/// `(zipmap keys vals)`: a map from each item of `keys` to the item at the
+/// same position in `vals`, stopping at the end of the shorter sequence.
+fn zipmap(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 2, 2)?;
+    let keys = expect_sequence_or_nil(&args[0])?;
+    let vals = expect_sequence_or_nil(&args[1])?;
+    keys.iter()
+        .zip(vals)
+        .map(|(key, val)| Ok((expect_key(key)?, val.clone())))
+        .collect::<Result<Map, _>>()
+        .map(Value::HashMap)
+}
+
+/// `(interleave coll...)`: a list of the first item of each `coll`, then the
+/// second of each, and so on, stopping at the end of the shortest.
+fn interleave(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    let colls = args
+        .iter()
+        .map(expect_sequence_or_nil)
+        .collect::<Result<Vec<_>, _>>()?;
+    let len = colls.iter().map(|coll| coll.len()).min().unwrap_or(0);
+    let items = (0..len)
+        .flat_map(|i| colls.iter().map(move |coll| coll[i].clone()))
+        .collect();
+    Ok(Value::list(items))
+}
+
+/// `(keys map)`: a list of the keys of `map` in insertion order.
+///
+/// The order always matches [vals], so the two can be zipped back together.
+fn keys(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    let map = expect_map(&args[0])?;
+    Ok(Value::list(map.keys().cloned().map(Value::Atom).collect()))
+}
+
+/// `(vals map)`: a list of the values of `map` in the same order as [keys].
+fn vals(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    let map = expect_map(&args[0])?;
+    Ok(Value::list(map.values().cloned().collect()))
+}
+
+fn pr_str_(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    Ok(Value::Atom(Atom::String(join_printed(args, false, " "))))
+}
+
+fn str_(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    Ok(Value::Atom(Atom::String(join_printed(args, true, ""))))
+}
+
+fn join(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 2, 2)?;
+    let separator = expect_string(&args[0])?;
+    let items = expect_sequence_or_nil(&args[1])?;
+    Ok(Value::Atom(Atom::String(join_printed(
+        items, true, separator,
+    ))))
+}
+
+/// `(format template arg...)`: `template` with each `{}` replaced by the next
+/// `arg`, printed as by `str`. `{{` and `}}` stand for literal braces.
+///
+/// There must be exactly one `arg` per `{}`.
+fn format_(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, usize::MAX)?;
+    let template = expect_string(&args[0])?;
+    let mut values = args[1..].iter();
+    let mut placeholders = 0;
+    let mut result = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                result.push(c);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                placeholders += 1;
+                if let Some(value) = values.next() {
+                    result.push_str(&pr_str(value, true));
+                }
+            }
+            ('{' | '}', _) => return Err(EvalError::InvalidFormat(format!("unmatched '{c}'"))),
+            _ => result.push(c),
+        }
+    }
+    if placeholders != args.len() - 1 {
+        return Err(EvalError::WrongArity {
+            expected: (placeholders + 1).to_string(),
+            got: args.len(),
+        });
+    }
+    Ok(Value::Atom(Atom::String(result)))
+}
+
+/// Write `line` to stdout and flush it, so that it appears before anything the
+/// program does next, such as blocking or writing to stderr.
+fn print_line(line: &str) -> Result<Value, EvalError> {
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{line}")
+        .and_then(|()| stdout.flush())
+        .map_err(|e| EvalError::Io {
+            path: "<stdout>".to_owned(),
+            message: e.to_string(),
+        })?;
+    Ok(Value::Atom(Atom::Nil))
+}
+
+fn prn(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    print_line(&join_printed(args, false, " "))
+}
+
+fn println_(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    print_line(&join_printed(args, true, " "))
+}
+
+/// Resolve `path` against the directory of the file being loaded, if it is
+/// relative and a file is being loaded.
+fn resolve_path(path: &str, env: &Env) -> PathBuf {
+    match env.file_dir() {
+        Some(dir) if Path::new(path).is_relative() => dir.join(path),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn read_file(path: &Path) -> Result<String, EvalError> {
+    fs::read_to_string(path).map_err(|error| EvalError::Io {
+        path: path.display().to_string(),
+        message: error.to_string(),
+    })
+}
+
+/// `(read-string source)`: the first form in `source`, unevaluated. Source
+/// containing no forms, such as an empty string or only a comment, reads as
+/// nil.
+fn read_string(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    match read_str(expect_string(&args[0])?) {
+        Ok(value) => Ok(value),
+        Err(ReadError::NoInput) => Ok(Value::Atom(Atom::Nil)),
+        Err(error) => Err(EvalError::Syntax(error)),
+    }
+}
+
+/// `(resolve sym)`: the value bound to `sym` where `resolve` is called, or nil
+/// if it is unbound. Use [bound?](is_bound) to tell an unbound symbol from one
+/// bound to nil.
+fn resolve(args: &[Value], env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    let sym = expect_symbol(&args[0])?;
+    Ok(env.get(sym).unwrap_or(Value::Atom(Atom::Nil)))
+}
+
+/// `(bound? sym)`: whether `sym` is bound where `bound?` is called.
+fn is_bound(args: &[Value], env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    let sym = expect_symbol(&args[0])?;
+    Ok(Value::from(env.get(sym).is_some()))
+}
+
+/// `(env-symbols)`: a sorted list of the names of every symbol bound where
+/// `env-symbols` is called, including local bindings and builtins.
+fn env_symbols(args: &[Value], env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 0, 0)?;
+    Ok(Value::list(
+        env.symbols().into_iter().map(Value::from).collect(),
+    ))
+}
+
+/// `(eval form)`: evaluate `form` in the root environment, so that any
+/// definitions it makes are visible globally rather than only where `eval` was
+/// called.
+fn eval_(args: &[Value], env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    eval(args[0].clone(), &env.root())
+}
+
+/// `(slurp path)`: the contents of the file at `path` as a string.
+///
+/// Relative paths are resolved like those given to `load-file`.
+fn slurp(args: &[Value], env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    let contents = read_file(&resolve_path(expect_string(&args[0])?, env))?;
+    Ok(Value::Atom(Atom::String(contents)))
+}
+
+/// `(load-file path)`: evaluate every form in the file at `path` in the root
+/// environment.
+///
+/// A relative path is resolved against the directory of the file currently
+/// being loaded, so scripts can load their siblings by name. At the top level
+/// it is resolved against the working directory.
+fn load_file(args: &[Value], env: &Env) -> Result<Value, EvalError> {
+    check_arity(args, 1, 1)?;
+    let path = resolve_path(expect_string(&args[0])?, env);
+    let forms = read_str_all(&read_file(&path)?).map_err(|error| EvalError::Read {
+        path: path.display().to_string(),
+        error,
+    })?;
+
+    let mut program = vec![Value::Atom(Atom::Symbol("do".into()))];
+    program.extend(forms);
+    let dir = path.parent().map(Path::to_path_buf);
+    let outer_dir = env.set_file_dir(dir);
+    let result = eval(Value::list(program), &env.root());
+    env.set_file_dir(outer_dir);
+    result?;
+    Ok(Value::Atom(Atom::Nil))
+}
+
+/// The names and implementations of all built-in functions.
+pub fn ns() -> Vec<(&'static str, NativeFn)> {
+    vec![
+        ("+", add),
+        ("-", subtract),
+        ("*", multiply),
+        ("/", divide),
+        ("<", less_than),
+        ("<=", less_than_or_equal),
+        (">", greater_than),
+        (">=", greater_than_or_equal),
+        ("=", equal),
+        ("not", not),
+        ("list", list),
+        ("list?", is_list),
+        ("nil?", is_nil),
+        ("true?", is_true),
+        ("false?", is_false),
+        ("some?", is_some),
+        ("not-nil?", is_some),
+        ("symbol", symbol),
+        ("symbol?", is_symbol),
+        ("keyword", keyword),
+        ("keyword?", is_keyword),
+        ("fn?", is_fn),
+        ("macro?", is_macro),
+        ("empty?", is_empty),
+        ("count", count),
+        ("hash-map", hash_map),
+        ("assoc", assoc),
+        ("dissoc", dissoc),
+        ("get", get),
+        ("get-in", get_in),
+        ("assoc-in", assoc_in),
+        ("update", update),
+        ("zipmap", zipmap),
+        ("interleave", interleave),
+        ("keys", keys),
+        ("vals", vals),
+        ("cons", cons),
+        ("conj", conj),
+        ("concat", concat),
+        ("into", into),
+        ("vec", vec),
+        ("first", first),
+        ("rest", rest),
+        ("nth", nth),
+        ("take", take),
+        ("drop", drop),
+        ("range", range),
+        ("apply", apply_),
+        ("map", map),
+        ("filter", filter),
+        ("remove", remove),
+        ("reduce", reduce),
+        ("sort", sort),
+        ("sort-by", sort_by),
+        ("gensym", gensym),
+        ("meta", meta),
+        ("with-meta", with_meta),
+        ("throw", throw),
+        ("trace", trace),
+        ("pr-str", pr_str_),
+        ("str", str_),
+        ("join", join),
+        ("format", format_),
+        ("prn", prn),
+        ("println", println_),
+        ("read-string", read_string),
+        ("eval", eval_),
+        ("resolve", resolve),
+        ("bound?", is_bound),
+        ("env-symbols", env_symbols),
+        ("slurp", slurp),
+        ("load-file", load_file),
+        ("atom", atom),
+        ("atom?", is_atom),
+        ("deref", deref),
+        ("reset!", reset),
+        ("swap!", swap),
+        ("time-ms", time_ms),
+        ("sleep", sleep),
+    ]
+}
+
+/// The set of native functions a root environment is created with.
+///
+/// The default namespace holds the functions from [ns]. Programs embedding mal
+/// can add their own before creating the environment.
+///
+/// # Examples
+///
+/// ```
+/// use mal::{core::CoreNamespace, env::Env, eval::EvalError, rep, types::Value};
+///
+/// fn shout(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+///     let text = String::try_from(args[0].clone())?;
+///     Ok(Value::from(text.to_uppercase()))
+/// }
+///
+/// let env = CoreNamespace::default().with("shout", shout).into_env();
+/// assert_eq!(rep("(shout \"hi\")", &env).unwrap(), "\"HI\"");
+/// ```
+pub struct CoreNamespace {
+    functions: Vec<(&'static str, NativeFn)>,
+}
+
+impl CoreNamespace {
+    /// Add `func` to the namespace as `name`, replacing any existing function
+    /// with that name.
+    pub fn with(mut self, name: &'static str, func: NativeFn) -> Self {
+        self.functions.push((name, func));
+        self
+    }
+
+    /// Create a new root environment containing every function in the
+    /// namespace, along with the standard macros such as `or` and `and`.
+    pub fn into_env(self) -> Env {
+        let env = Env::new();
+        for (name, func) in self.functions {
+            env.register(name, func);
+        }
+        for form in read_str_all(BOOTSTRAP).expect("bootstrap source is valid") {
+            eval(form, &env).expect("bootstrap source evaluates");
+        }
+        env
+    }
+}
+
+impl Default for CoreNamespace {
+    fn default() -> Self {
+        Self { functions: ns() }
+    }
+}
+
+/// Create a new root environment containing all built-in functions, along
+/// with the standard macros such as `or` and `and`.
+///
+/// This is the same as `CoreNamespace::default().into_env()`.
+pub fn default_env() -> Env {
+    CoreNamespace::default().into_env()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::default_env;
+    use crate::{
+        eval::{eval, EvalError},
+        map::Map,
+        reader::{read_str, ReadError},
+        types::{Atom, Value},
+    };
+
+    fn rep(input: &str) -> Result<Value, EvalError> {
+        eval(read_str(input).unwrap(), &default_env())
+    }
+
+    #[test]
+    fn test_not() {
+        assert_eq!(rep("(not nil)"), Ok(Value::Atom(Atom::True)));
+        assert_eq!(rep("(not false)"), Ok(Value::Atom(Atom::True)));
+        assert_eq!(rep("(not 0)"), Ok(Value::Atom(Atom::False)));
+    }
+
+    #[test]
+    fn test_not_agrees_with_if() {
+        for input in ["nil", "false", "true", "0", "\"\"", "()"] {
+            let via_if = rep(&format!("(if {input} false true)"));
+            let via_not = rep(&format!("(not {input})"));
+            assert_eq!(via_if, via_not, "disagreement on {input}");
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(rep("(+ 1 2 3)"), Ok(Value::Atom(Atom::Int(6))));
+        assert_eq!(rep("(- 10 4 1)"), Ok(Value::Atom(Atom::Int(5))));
+        assert_eq!(rep("(* 2 3 4)"), Ok(Value::Atom(Atom::Int(24))));
+        assert_eq!(rep("(/ 7 2)"), Ok(Value::Atom(Atom::Int(3))));
+        assert_eq!(rep("(/ 1 0)"), Err(EvalError::DivisionByZero));
+    }
+
+    #[test]
+    fn test_mixed_arithmetic() {
+        assert_eq!(rep("(+ 1 2.5)"), Ok(Value::from(3.5)));
+        assert_eq!(rep("(+ 1 2)"), Ok(Value::from(3)));
+        assert_eq!(rep("(- 1.5)"), Ok(Value::from(-1.5)));
+        assert_eq!(rep("(* 2 1.5 2)"), Ok(Value::from(6.0)));
+        assert_eq!(rep("(/ 1 2.0)"), Ok(Value::from(0.5)));
+        assert_eq!(rep("(/ 1 0.0)"), Ok(Value::from(f64::INFINITY)));
+        assert_eq!(rep("(< 1 1.5)"), Ok(Value::Atom(Atom::True)));
+        assert_eq!(rep("(>= 2 2.0)"), Ok(Value::Atom(Atom::True)));
+        assert_eq!(rep("(> 1.5 2)"), Ok(Value::Atom(Atom::False)));
+        assert!(rep("(+ 1.5 :a)").is_err());
+    }
+
+    #[test]
+    fn test_integer_overflow() {
+        assert_eq!(
+            rep("(* 1000000000000 1000000000000)"),
+            Err(EvalError::IntegerOverflow)
+        );
+        assert_eq!(
+            rep("(* 1000000000 1000000000)"),
+            Ok(Value::Atom(Atom::Int(1_000_000_000_000_000_000)))
+        );
+        assert_eq!(
+            rep("(try* (* 1000000000000 1000000000000) (catch* e e))"),
+            Ok(Value::from("integer overflow"))
+        );
+        assert_eq!(
+            rep("(+ 9223372036854775807 1)"),
+            Err(EvalError::IntegerOverflow)
+        );
+        assert_eq!(
+            rep("(* 4611686018427387904 2)"),
+            Err(EvalError::IntegerOverflow)
+        );
+        assert_eq!(
+            rep("(- (- 0 9223372036854775807 1))"),
+            Err(EvalError::IntegerOverflow)
+        );
+        assert_eq!(
+            rep("(/ (- 0 9223372036854775807 1) -1)"),
+            Err(EvalError::IntegerOverflow)
+        );
+        assert_eq!(
+            rep("(+ 9223372036854775807 1.0)"),
+            Ok(Value::from(9223372036854775808.0))
+        );
+    }
+
+    #[test]
+    fn test_equal() {
+        assert_eq!(rep("(= (list 1 2) [1 2])"), Ok(Value::Atom(Atom::True)));
+        assert_eq!(
+            rep("(= {:a [1]} {:a (list 1)})"),
+            Ok(Value::Atom(Atom::True))
+        );
+        assert_eq!(rep("(= 1 \"1\")"), Ok(Value::Atom(Atom::False)));
+    }
+
+    #[test]
+    fn test_str_and_pr_str() {
+        assert_eq!(
+            rep("(pr-str \"a\" :b 1)"),
+            Ok(Value::Atom(Atom::String("\"a\" :b 1".to_owned())))
+        );
+        assert_eq!(
+            rep("(str \"a\" :b 1)"),
+            Ok(Value::Atom(Atom::String("a:b1".to_owned())))
+        );
+        assert_eq!(rep("(str :a)"), Ok(Value::from(":a")));
+        assert_eq!(rep("(pr-str :a)"), Ok(Value::from(":a")));
+        assert_eq!(rep("(str (list 1 \"a\" :b))"), Ok(Value::from("(1 a :b)")));
+        assert_eq!(
+            rep("(str [\"x\" (list \"y\" {\"k\" \"v\"})])"),
+            Ok(Value::from("[x (y {k v})]"))
+        );
+        assert_eq!(rep("(pr-str (list \"a\"))"), Ok(Value::from("(\"a\")")));
+    }
+
+    #[test]
+    fn test_str_and_pr_str_without_arguments() {
+        assert_eq!(rep("(str)"), Ok(Value::from("")));
+        assert_eq!(rep("(pr-str)"), Ok(Value::from("")));
+        assert_eq!(rep("(str (str) (pr-str))"), Ok(Value::from("")));
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            rep("(format \"{} + {} = {}\" 1 2 3)"),
+            Ok(Value::from("1 + 2 = 3"))
+        );
+        assert_eq!(
+            rep("(format \"{}: {}\" \"name\" [\"a\" :b])"),
+            Ok(Value::from("name: [a :b]"))
+        );
+        assert_eq!(rep("(format \"{{{}}} }}{{\" 1)"), Ok(Value::from("{1} }{")));
+        assert_eq!(rep("(format \"plain\")"), Ok(Value::from("plain")));
+        assert_eq!(
+            rep("(format \"{} {}\" 1)"),
+            Err(EvalError::WrongArity {
+                expected: "3".to_owned(),
+                got: 2
+            })
+        );
+        assert_eq!(
+            rep("(format \"{}\" 1 2)"),
+            Err(EvalError::WrongArity {
+                expected: "2".to_owned(),
+                got: 3
+            })
+        );
+        assert_eq!(
+            rep("(format \"{ }\")"),
+            Err(EvalError::InvalidFormat("unmatched '{'".to_owned()))
+        );
+        assert_eq!(
+            rep("(format \"}\")"),
+            Err(EvalError::InvalidFormat("unmatched '}'".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_join() {
+        assert_eq!(
+            rep("(join \", \" [1 \"two\" :three])"),
+            Ok(Value::Atom(Atom::String("1, two, :three".to_owned())))
+        );
+        assert_eq!(
+            rep("(join \", \" ())"),
+            Ok(Value::Atom(Atom::String(String::new())))
+        );
+        assert!(rep("(join 1 [])").is_err());
+    }
+
+    #[test]
+    fn test_read_string() {
+        assert_eq!(
+            rep("(read-string \"(+ 1 2)\")"),
+            Ok(read_str("(+ 1 2)").unwrap())
+        );
+        assert_eq!(rep("(read-string \"\")"), Ok(Value::Atom(Atom::Nil)));
+        assert_eq!(rep("(read-string \"  \")"), Ok(Value::Atom(Atom::Nil)));
+        assert_eq!(
+            rep("(read-string \"; comment\")"),
+            Ok(Value::Atom(Atom::Nil))
+        );
+        assert_eq!(
+            rep("(read-string \"(1 2\")"),
+            Err(EvalError::Syntax(ReadError::UnexpectedEndOfInput(4)))
+        );
+    }
+
+    #[test]
+    fn test_eval_uses_root_env() {
+        let env = default_env();
+        let rep = |input| eval(read_str(input).unwrap(), &env);
+        rep("(eval (read-string \"(def! x 10)\"))").unwrap();
+        assert_eq!(rep("x"), Ok(Value::Atom(Atom::Int(10))));
+
+        rep("(def! define-y (fn* (y) (eval (list 'def! 'y y))))").unwrap();
+        rep("(define-y 20)").unwrap();
+        assert_eq!(rep("y"), Ok(Value::Atom(Atom::Int(20))));
+        assert_eq!(
+            rep("(let* (z 1) (eval 'z))"),
+            Err(EvalError::UnboundSymbol("z".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_load_file() {
+        let path = env::temp_dir().join("mal_test_load_file.mal");
+        fs::write(
+            &path,
+            "(def! double (fn* (x) (* x 2)))\n(def! result (double 21))\n",
+        )
+        .unwrap();
+
+        let env = default_env();
+        let load = format!("(load-file \"{}\")", path.display());
+        assert_eq!(
+            eval(read_str(&load).unwrap(), &env),
+            Ok(Value::Atom(Atom::Nil))
+        );
+        assert_eq!(
+            eval(read_str("(double result)").unwrap(), &env),
+            Ok(Value::Atom(Atom::Int(84)))
+        );
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_load_file_reports_path() {
+        let path = env::temp_dir().join("mal_test_load_file_error.mal");
+        fs::write(&path, "(def! a").unwrap();
+
+        let load = format!("(load-file \"{}\")", path.display());
+        match rep(&load) {
+            Err(error @ EvalError::Read { .. }) => {
+                assert!(error.to_string().starts_with(&path.display().to_string()))
+            }
+            other => panic!("expected a read error, got {other:?}"),
+        }
+        assert!(matches!(
+            rep("(load-file \"/does/not/exist.mal\")"),
+            Err(EvalError::Io { path, .. }) if path == "/does/not/exist.mal"
+        ));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_throw() {
+        assert_eq!(
+            rep("(throw {:msg \"oops\"})"),
+            Err(EvalError::Thrown(read_str("{:msg \"oops\"}").unwrap()))
+        );
+    }
+
+    #[test]
+    fn test_gensym_is_unique() {
+        assert_eq!(rep("(= (gensym) (gensym))"), Ok(Value::Atom(Atom::False)));
+    }
+
+    #[test]
+    fn test_list_functions() {
+        assert_eq!(rep("(cons 1 [2 3])"), rep("(list 1 2 3)"));
+        assert_eq!(rep("(first [1 2])"), Ok(Value::Atom(Atom::Int(1))));
+        assert_eq!(rep("(first nil)"), Ok(Value::Atom(Atom::Nil)));
+        assert_eq!(rep("(rest (list 1 2))"), rep("(list 2)"));
+        assert_eq!(rep("(rest [])"), rep("(list)"));
+    }
+
+    #[test]
+    fn test_sequence_builtins_reject_non_sequences() {
+        assert_eq!(rep("(empty? nil)"), Ok(Value::Atom(Atom::True)));
+        for input in ["(empty? 5)", "(nth 5 0)"] {
+            assert_eq!(
+                rep(input),
+                Err(EvalError::TypeMismatch {
+                    expected: "sequence",
+                    got: "int".to_owned()
+                }),
+                "{input}"
+            );
+        }
+        assert_eq!(
+            rep("(empty? {:a 1})"),
+            Err(EvalError::TypeMismatch {
+                expected: "sequence",
+                got: "hashmap".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn test_count() {
+        let env = default_env();
+        let rep = |input| eval(read_str(input).unwrap(), &env);
+        let int = |n| Ok(Value::Atom(Atom::Int(n)));
+        assert_eq!(rep("(count (list 1 2 3))"), int(3));
+        assert_eq!(rep("(count [1 2])"), int(2));
+        assert_eq!(rep("(count \"héllo\")"), int(5));
+        assert_eq!(rep("(count \"\")"), int(0));
+        assert_eq!(rep("(count nil)"), int(0));
+        rep("(def! m {:a 1 :b 2})").unwrap();
+        assert_eq!(rep("(count m)"), int(2));
+        assert_eq!(rep("(= (count m) (count (keys m)))"), rep("true"));
+        for input in ["(count 5)", "(count :a)", "(count +)"] {
+            assert!(
+                matches!(
+                    rep(input),
+                    Err(EvalError::TypeMismatch {
+                        expected: "collection",
+                        ..
+                    })
+                ),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_count_and_empty_do_not_walk_the_collection() {
+        let env = default_env();
+        let rep = |input: &str| eval(read_str(input).unwrap(), &env);
+        rep("(def! xs (range 200000))").unwrap();
+        rep("(def! v (vec xs))").unwrap();
+        rep("(def! s (apply str (map (fn* [_] \"x\") xs)))").unwrap();
+        // Walking the collection on every call would make each loop take
+        // billions of steps rather than finishing immediately.
+        rep("(def! spin (fn* [n coll acc]
+               (if (= n 0)
+                 acc
+                 (spin (- n 1) coll (if (empty? coll) acc (+ acc (count coll)))))))")
+        .unwrap();
+        for coll in ["xs", "v"] {
+            assert_eq!(
+                rep(&format!("(spin 20000 {coll} 0)")),
+                Ok(Value::from(4_000_000_000))
+            );
+        }
+        assert_eq!(rep("(empty? s)"), rep("false"));
+        assert_eq!(rep("(empty? \"\")"), rep("true"));
+        assert_eq!(rep("(empty? nil)"), rep("true"));
+        assert!(matches!(
+            rep("(empty? 1)"),
+            Err(EvalError::TypeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_sequence_access_on_vectors() {
+        assert_eq!(rep("(rest [1 2 3])"), rep("(list 2 3)"));
+        assert_eq!(rep("(list? (rest [1 2 3]))"), Ok(Value::Atom(Atom::True)));
+        assert_eq!(rep("(list? (rest []))"), Ok(Value::Atom(Atom::True)));
+        assert_eq!(rep("(first [])"), Ok(Value::Atom(Atom::Nil)));
+        assert_eq!(rep("(first ())"), Ok(Value::Atom(Atom::Nil)));
+        assert_eq!(rep("(nth [1 2 3] 1)"), Ok(Value::Atom(Atom::Int(2))));
+        assert_eq!(rep("(nth (list 1 2 3) 1)"), Ok(Value::Atom(Atom::Int(2))));
+    }
+
+    #[test]
+    fn test_sequence_constructors_are_fresh() {
+        let env = default_env();
+        let rep = |input: &str| eval(read_str(input).unwrap(), &env);
+        rep("(def! xs (with-meta (list 1 2) {:a 1}))").unwrap();
+        rep("(def! v (with-meta [1 2] {:a 1}))").unwrap();
+
+        assert_eq!(rep("(conj xs 3 4)"), Ok(read_str("(4 3 1 2)").unwrap()));
+        assert_eq!(rep("(conj v 3 4)"), Ok(read_str("[1 2 3 4]").unwrap()));
+        assert!(matches!(rep("(conj v 3)"), Ok(Value::Vector(_))));
+        assert_eq!(rep("(cons 0 v)"), Ok(read_str("(0 1 2)").unwrap()));
+        assert_eq!(
+            rep("(concat xs nil v [])"),
+            Ok(read_str("(1 2 1 2)").unwrap())
+        );
+        assert_eq!(rep("(concat)"), Ok(Value::list(vec![])));
+
+        for call in ["(conj xs 3)", "(conj v 3)", "(cons 0 xs)", "(concat v)"] {
+            assert_eq!(
+                rep(&format!("(meta {call})")),
+                Ok(Value::Atom(Atom::Nil)),
+                "{call}"
+            );
+        }
+        assert_eq!(rep("xs"), Ok(read_str("(1 2)").unwrap()));
+        assert_eq!(rep("v"), Ok(read_str("[1 2]").unwrap()));
+        assert_eq!(rep("(meta xs)"), Ok(read_str("{:a 1}").unwrap()));
+        assert!(rep("(conj {} 1)").is_err());
+    }
+
+    #[test]
+    fn test_meta() {
+        let env = default_env();
+        let rep = |input| eval(read_str(input).unwrap(), &env);
+        assert_eq!(rep("(meta [1])"), Ok(Value::Atom(Atom::Nil)));
+        assert_eq!(rep("(meta ^{:a 1} [1])"), Ok(read_str("{:a 1}").unwrap()));
+        assert_eq!(
+            rep("(meta (with-meta {:k 1} :m))"),
+            Ok(read_str(":m").unwrap())
+        );
+        rep("(def! f (fn* (x) x))").unwrap();
+        rep("(def! g (with-meta f {:doc \"id\"}))").unwrap();
+        assert_eq!(rep("(meta f)"), Ok(Value::Atom(Atom::Nil)));
+        assert_eq!(rep("(meta g)"), Ok(read_str("{:doc \"id\"}").unwrap()));
+        assert_eq!(rep("(g 5)"), Ok(Value::Atom(Atom::Int(5))));
+        assert_eq!(rep("((with-meta + 1) 1 2)"), Ok(Value::Atom(Atom::Int(3))));
+        assert_eq!(rep("(meta 1)"), Ok(Value::Atom(Atom::Nil)));
+        assert!(rep("(with-meta 1 {})").is_err());
+    }
+
+    #[test]
+    fn test_equality_ignores_meta() {
+        let t = Ok(Value::Atom(Atom::True));
+        assert_eq!(rep("(= (with-meta [1 2] {:a 1}) [1 2])"), t);
+        assert_eq!(rep("(= (list 1 2) (with-meta (list 1 2) {:a 1}))"), t);
+        assert_eq!(rep("(= (with-meta {:k 1} {:a 1}) {:k 1})"), t);
+        assert_eq!(rep("(= (with-meta [1] :x) (with-meta [1] :y))"), t);
+        assert_eq!(rep("(= [(with-meta [1] :x)] [[1]])"), t);
+        assert_eq!(rep("(= ^{:a 1} [1 2] (list 1 2))"), t);
+        assert_eq!(
+            rep("(= (with-meta [1 2] {:a 1}) [1 3])"),
+            Ok(Value::Atom(Atom::False))
+        );
+        for input in ["[1 2]", "(list 1 2)", "{:k [1]}"] {
+            assert_eq!(
+                rep(&format!("(with-meta {input} {{:a 1}})")),
+                rep(input),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        let env = default_env();
+        let rep = |input: &str| eval(read_str(input).unwrap(), &env);
+        rep("(def! x 42)").unwrap();
+        rep("(def! nothing nil)").unwrap();
+        assert_eq!(rep("(resolve 'x)"), Ok(Value::from(42)));
+        assert_eq!(rep("(resolve 'undefined)"), Ok(Value::Atom(Atom::Nil)));
+        assert_eq!(rep("(bound? 'x)"), Ok(Value::Atom(Atom::True)));
+        assert_eq!(rep("(bound? 'nothing)"), Ok(Value::Atom(Atom::True)));
+        assert_eq!(rep("(bound? 'undefined)"), Ok(Value::Atom(Atom::False)));
+        assert_eq!(rep("(let* (y 1) (resolve 'y))"), Ok(Value::from(1)));
+        assert_eq!(rep("(fn? (resolve '+))"), Ok(Value::Atom(Atom::True)));
+        assert_eq!(
+            rep("(resolve \"x\")"),
+            Err(EvalError::TypeMismatch {
+                expected: "symbol",
+                got: "string".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn test_env_symbols() {
+        let env = default_env();
+        let rep = |input: &str| eval(read_str(input).unwrap(), &env);
+        rep("(def! zeta 1)").unwrap();
+        rep("(def! alpha 2)").unwrap();
+        let Ok(Value::List(names)) = rep("(env-symbols)") else {
+            panic!("expected a list");
+        };
+        assert!(names.contains(&Value::from("alpha")));
+        assert!(names.contains(&Value::from("zeta")));
+        assert!(names.contains(&Value::from("env-symbols")));
+        assert_eq!(
+            rep("(= (env-symbols) (sort (env-symbols)))"),
+            Ok(Value::from(true))
+        );
+        assert_eq!(
+            rep("(let* (local 1) (count (filter (fn* (s) (= s \"local\")) (env-symbols))))"),
+            Ok(Value::from(1))
+        );
+    }
+
+    #[test]
+    fn test_type_predicates() {
+        let env = default_env();
+        let rep = |input: &str| eval(read_str(input).unwrap(), &env);
+        rep("(def! f (fn* (x) x))").unwrap();
+        rep("(defmacro! m (fn* (x) x))").unwrap();
+
+        let values = ["'abc", ":abc", "\"abc\"", "\":abc\"", "f", "+", "m", "nil"];
+        let cases = [
+            (
+                "symbol?",
+                [true, false, false, false, false, false, false, false],
+            ),
+            (
+                "keyword?",
+                [false, true, false, false, false, false, false, false],
+            ),
+            (
+                "fn?",
+                [false, false, false, false, true, true, false, false],
+            ),
+            (
+                "macro?",
+                [false, false, false, false, false, false, true, false],
+            ),
+        ];
+        for (predicate, expected) in cases {
+            for (value, expected) in values.iter().zip(expected) {
+                assert_eq!(
+                    rep(&format!("({predicate} {value})")),
+                    Ok(Value::from(expected)),
+                    "({predicate} {value})"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_strings_as_sequences() {
+        let c = |c| Ok(Value::Atom(Atom::Char(c)));
+        assert_eq!(rep("(first \"abc\")"), c('a'));
+        assert_eq!(rep("(nth \"abc\" 1)"), c('b'));
+        assert_eq!(rep("(rest \"abc\")"), rep("(list \\b \\c)"));
+        assert_eq!(rep("(count \"abc\")"), Ok(Value::Atom(Atom::Int(3))));
+        assert_eq!(rep("(first \"\")"), Ok(Value::Atom(Atom::Nil)));
+        assert_eq!(rep("(rest \"\")"), rep("(list)"));
+        assert_eq!(rep("(empty? \"\")"), Ok(Value::Atom(Atom::True)));
+
+        // "é" and "λ" take two bytes each, so byte indexing would go wrong.
+        assert_eq!(rep("(nth \"éλx\" 1)"), c('λ'));
+        assert_eq!(rep("(nth \"éλx\" 2)"), c('x'));
+        assert_eq!(rep("(count \"éλx\")"), Ok(Value::Atom(Atom::Int(3))));
+        assert_eq!(rep("(first \"λ\")"), c('λ'));
+        assert_eq!(
+            rep("(nth \"éλx\" 3)"),
+            Err(EvalError::IndexOutOfRange { index: 3, len: 3 })
+        );
+        assert_eq!(rep("(nth \"abc\" 5 :none)"), rep(":none"));
+        assert_eq!(rep("(nth \"abc\" -1 :none)"), rep(":none"));
+    }
+
+    #[test]
+    fn test_first_and_nth_do_not_copy_strings() {
+        let env = default_env();
+        let rep = |input: &str| eval(read_str(input).unwrap(), &env);
+        rep("(def! s (apply str (map (fn* [_] \"x\") (range 200000))))").unwrap();
+        // Copying the string on every call would make this take billions of
+        // steps rather than finishing immediately.
+        rep("(def! spin (fn* [n acc]
+               (if (= n 0)
+                 acc
+                 (spin (- n 1) (if (= (first s) (nth s 2)) (+ acc 1) acc)))))")
+        .unwrap();
+        assert_eq!(rep("(spin 20000 0)"), Ok(Value::from(20000)));
+    }
+
+    #[test]
+    fn test_take_and_drop() {
+        assert_eq!(rep("(take 2 [1 2 3])"), rep("(list 1 2)"));
+        assert_eq!(rep("(take 5 [1 2 3])"), rep("(list 1 2 3)"));
+        assert_eq!(rep("(take -1 [1 2 3])"), rep("(list)"));
+        assert_eq!(rep("(take 2 nil)"), rep("(list)"));
+        assert_eq!(rep("(list? (take 2 [1 2 3]))"), Ok(Value::Atom(Atom::True)));
+
+        assert_eq!(rep("(drop 1 (list 1 2 3))"), rep("(list 2 3)"));
+        assert_eq!(rep("(drop 3 [1 2 3])"), rep("(list)"));
+        assert_eq!(rep("(drop 10 [1 2 3])"), rep("(list)"));
+        assert_eq!(rep("(drop -2 [1 2 3])"), rep("(list 1 2 3)"));
+        assert_eq!(rep("(list? (drop 1 [1 2 3]))"), Ok(Value::Atom(Atom::True)));
+    }
+
+    #[test]
+    fn test_range() {
+        assert_eq!(rep("(range 4)"), rep("(list 0 1 2 3)"));
+        assert_eq!(rep("(range 0)"), rep("(list)"));
+        assert_eq!(rep("(range -3)"), rep("(list)"));
+        assert_eq!(rep("(range 2 5)"), rep("(list 2 3 4)"));
+        assert_eq!(rep("(range 5 2)"), rep("(list)"));
+        assert_eq!(rep("(range 0 10 3)"), rep("(list 0 3 6 9)"));
+        assert_eq!(rep("(range 5 0 -2)"), rep("(list 5 3 1)"));
+        assert_eq!(rep("(range 0 5 -1)"), rep("(list)"));
+        assert_eq!(rep("(range 1 5 0)"), Err(EvalError::ZeroStep));
+        assert_eq!(
+            rep("(range 9223372036854775806 9223372036854775807 5)"),
+            rep("(list 9223372036854775806)")
+        );
+    }
+
+    #[test]
+    fn test_sort() {
+        assert_eq!(rep("(sort [3 1 2])"), rep("(list 1 2 3)"));
+        assert_eq!(rep("(sort (list 2.5 -1 2))"), rep("(list -1 2 2.5)"));
+        assert_eq!(
+            rep("(sort [\"pear\" \"apple\"])"),
+            rep("(list \"apple\" \"pear\")")
+        );
+        assert_eq!(rep("(sort [])"), rep("(list)"));
+        assert_eq!(
+            rep("(sort [1 \"a\"])"),
+            Err(EvalError::TypeMismatch {
+                expected: "int",
+                got: "string".to_owned()
+            })
+        );
+        assert_eq!(
+            rep("(sort [\"a\" 1])"),
+            Err(EvalError::TypeMismatch {
+                expected: "string",
+                got: "int".to_owned()
+            })
+        );
+        assert!(rep("(sort [:a :b])").is_err());
+    }
+
+    #[test]
+    fn test_sort_by() {
+        assert_eq!(
+            rep("(sort-by (fn* (m) (get m :age)) [{:age 30} {:age 20}])"),
+            rep("(list {:age 20} {:age 30})")
+        );
+        assert_eq!(
+            rep("(sort-by count [[1 2] [3] [4 5] [6]])"),
+            rep("(list [3] [6] [1 2] [4 5])")
+        );
+        assert_eq!(
+            rep("(sort-by first [[2 :x] [1 :y] [2 :a] [1 :b]])"),
+            rep("(list [1 :y] [1 :b] [2 :x] [2 :a])")
+        );
+    }
+
+    #[test]
+    fn test_get_default() {
+        assert_eq!(rep("(get {:a 1} :a 0)"), Ok(Value::from(1)));
+        assert_eq!(rep("(get {:a 1} :b 0)"), Ok(Value::from(0)));
+        assert_eq!(rep("(get nil :b 0)"), Ok(Value::from(0)));
+        assert_eq!(rep("(get {:a nil} :a 0)"), Ok(Value::Atom(Atom::Nil)));
+    }
+
+    #[test]
+    fn test_get_in() {
+        let data = "{:users [{:name \"ann\"} {:name \"bob\" :tags (list :x :y)}]}";
+        let get_in = |path: &str| rep(&format!("(get-in {data} {path})"));
+        assert_eq!(get_in("[:users 1 :name]"), Ok(Value::from("bob")));
+        assert_eq!(get_in("[:users 1 :tags 1]"), rep(":y"));
+        assert_eq!(get_in("[]"), rep(data));
+        assert_eq!(get_in("[:users 5 :name]"), Ok(Value::Atom(Atom::Nil)));
+        assert_eq!(get_in("[:users -1]"), Ok(Value::Atom(Atom::Nil)));
+        assert_eq!(get_in("[:groups 0 :name]"), Ok(Value::Atom(Atom::Nil)));
+        assert_eq!(get_in("[:users 0 :tags 0]"), Ok(Value::Atom(Atom::Nil)));
+        assert_eq!(get_in("[:users 0 :age] 42"), Ok(Value::from(42)));
+        assert_eq!(
+            get_in("[:users :first]"),
+            Err(EvalError::TypeMismatch {
+                expected: "int",
+                got: "keyword".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn test_assoc_in() {
+        assert_eq!(rep("(assoc-in {:a {:b 1}} [:a :b] 2)"), rep("{:a {:b 2}}"));
+        assert_eq!(
+            rep("(assoc-in {:a 1} [:x :y :z] 2)"),
+            rep("{:a 1 :x {:y {:z 2}}}")
+        );
+        assert_eq!(rep("(assoc-in nil [:a] 1)"), rep("{:a 1}"));
+        assert_eq!(
+            rep("(assoc-in {:v [{:n 1} {:n 2}]} [:v 1 :n] 3)"),
+            rep("{:v [{:n 1} {:n 3}]}")
+        );
+        assert_eq!(
+            rep("(assoc-in [1 2] [2] 3)"),
+            Err(EvalError::IndexOutOfRange { index: 2, len: 2 })
+        );
+        assert_eq!(
+            rep("(let* (m {:a {:b 1}} n (assoc-in m [:a :b] 2)) m)"),
+            rep("{:a {:b 1}}")
+        );
+    }
+
+    #[test]
+    fn test_update() {
+        assert_eq!(rep("(update {:n 1} :n (fn* (x) (+ x 1)))"), rep("{:n 2}"));
+        assert_eq!(rep("(update {:n 1} :n + 10 100)"), rep("{:n 111}"));
+        assert_eq!(
+            rep("(update {} :n (fn* (x) (if x :present :missing)))"),
+            rep("{:n :missing}")
+        );
+        assert_eq!(rep("(update nil :n list)"), rep("{:n (list nil)}"));
+        assert_eq!(rep("(let* (m {:n 1} u (update m :n -)) m)"), rep("{:n 1}"));
+    }
+
+    #[test]
+    fn test_zipmap() {
+        assert_eq!(rep("(zipmap (list :a :b) (list 1 2))"), rep("{:a 1 :b 2}"));
+        assert_eq!(rep("(zipmap [:a :b :c] [1])"), rep("{:a 1}"));
+        assert_eq!(rep("(zipmap [:a] [1 2 3])"), rep("{:a 1}"));
+        assert_eq!(rep("(zipmap [] [1])"), rep("{}"));
+        assert_eq!(
+            rep("(zipmap [[1]] [2])"),
+            Err(EvalError::UnhashableType(rep("[1]").unwrap()))
+        );
+    }
+
+    #[test]
+    fn test_interleave() {
+        assert_eq!(
+            rep("(interleave [1 2 3] (list :a :b :c))"),
+            rep("(list 1 :a 2 :b 3 :c)")
+        );
+        assert_eq!(
+            rep("(interleave [1 2 3] [:a] [\"x\" \"y\"])"),
+            rep("(list 1 :a \"x\")")
+        );
+        assert_eq!(rep("(interleave [1 2] [])"), rep("(list)"));
+        assert_eq!(rep("(interleave)"), rep("(list)"));
+    }
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(rep("(apply + (list 1 2))"), Ok(Value::from(3)));
+        assert_eq!(rep("(apply + 1 2 [3 4])"), Ok(Value::from(10)));
+        assert_eq!(rep("(apply list [])"), rep("(list)"));
+        assert_eq!(rep("(apply (fn* (& xs) xs) 1 (list 2))"), rep("(list 1 2)"));
+        assert_eq!(
+            rep("(apply + 1 2 3)"),
+            Err(EvalError::TypeMismatch {
+                expected: "list or vector as last argument",
+                got: "int".to_owned()
+            })
+        );
+        assert_eq!(
+            rep("(do (defmacro! m (fn* (x) x)) (apply m [1]))"),
+            Err(EvalError::ApplyMacro)
+        );
+        assert_eq!(
+            rep("(try* (map or [1]) (catch* e e))"),
+            Ok(Value::from("cannot apply a macro"))
+        );
+        assert!(matches!(
+            rep("(apply +)"),
+            Err(EvalError::WrongArity { .. })
+        ));
+    }
+
+    #[test]
+    fn test_vec() {
+        assert_eq!(rep("(vec (list 1 2 3))"), rep("[1 2 3]"));
+        assert_eq!(rep("(vec (list))"), rep("[]"));
+        assert_eq!(rep("(vec [1 2])"), rep("[1 2]"));
+        assert_eq!(rep("(meta (vec ^:m [1]))"), rep(":m"));
+        assert_eq!(
+            rep("(vec 1)"),
+            Err(EvalError::TypeMismatch {
+                expected: "sequence",
+                got: "int".to_owned()
+            })
+        );
+        assert!(rep("(vec {:a 1})").is_err());
+    }
+
+    #[test]
+    fn test_into() {
+        assert_eq!(rep("(into [] (list 1 2))"), rep("[1 2]"));
+        assert_eq!(rep("(into [0] [1 2])"), rep("[0 1 2]"));
+        assert_eq!(rep("(into (list) [1 2])"), rep("(list 2 1)"));
+        assert_eq!(rep("(into (list 0) [1 2])"), rep("(list 2 1 0)"));
+        assert_eq!(rep("(into {} (list [:a 1] [:b 2]))"), rep("{:a 1 :b 2}"));
+        assert_eq!(rep("(into {:a 0} [[:a 1]])"), rep("{:a 1}"));
+        assert_eq!(rep("(into [] {:a 1})"), rep("[[:a 1]]"));
+        assert_eq!(rep("(into [1] nil)"), rep("[1]"));
+        assert_eq!(
+            rep("(into {} [[:a 1 2]])"),
+            Err(EvalError::TypeMismatch {
+                expected: "key/value pair",
+                got: "vector".to_owned()
+            })
+        );
+        assert!(rep("(into 1 [1])").is_err());
+    }
+
+    #[test]
+    fn test_nil_and_boolean_predicates() {
+        let t = Ok(Value::Atom(Atom::True));
+        let f = Ok(Value::Atom(Atom::False));
+        assert_eq!(rep("(nil? nil)"), t);
+        assert_eq!(rep("(nil? false)"), f);
+        assert_eq!(rep("(nil? (list))"), f);
+        assert_eq!(rep("(some? false)"), t);
+        assert_eq!(rep("(some? 0)"), t);
+        assert_eq!(rep("(some? nil)"), f);
+        assert_eq!(rep("(not-nil? false)"), t);
+        assert_eq!(rep("(not-nil? nil)"), f);
+        assert_eq!(rep("(true? true)"), t);
+        assert_eq!(rep("(true? 1)"), f);
+        assert_eq!(rep("(false? false)"), t);
+        assert_eq!(rep("(false? nil)"), f);
+        assert_eq!(rep("(if false :truthy :falsey)"), rep(":falsey"));
+    }
+
+    #[test]
+    fn test_symbol_and_keyword() {
+        assert_eq!(rep("(symbol \"abc\")"), rep("'abc"));
+        assert_eq!(rep("(keyword \"abc\")"), rep(":abc"));
+        assert_eq!(rep("(keyword :abc)"), rep(":abc"));
+        let blank = Err(EvalError::TypeMismatch {
+            expected: "non-blank string",
+            got: "blank string".to_owned(),
+        });
+        for name in ["\"\"", "\" \"", "\"\\n\\x09\""] {
+            assert_eq!(rep(&format!("(symbol {name})")), blank, "{name}");
+            assert_eq!(rep(&format!("(keyword {name})")), blank, "{name}");
+        }
+        assert!(rep("(symbol 1)").is_err());
+    }
+
+    #[test]
+    fn test_nth_default() {
+        assert_eq!(rep("(nth [1 2 3] 1 :none)"), Ok(Value::from(2)));
+        assert_eq!(rep("(nth [1 2 3] 3 :none)"), rep(":none"));
+        assert_eq!(rep("(nth (list 1) -1 :none)"), rep(":none"));
+        assert_eq!(rep("(nth \"ab\" 5 nil)"), Ok(Value::Atom(Atom::Nil)));
+        assert_eq!(rep("(nth [nil] 0 :none)"), Ok(Value::Atom(Atom::Nil)));
+        assert_eq!(
+            rep("(nth [1 2 3] 3)"),
+            Err(EvalError::IndexOutOfRange { index: 3, len: 3 })
+        );
+    }
+
+    #[test]
+    fn test_map_several_collections() {
+        assert_eq!(rep("(map + (list 1 2) (list 10 20))"), rep("(list 11 22)"));
+        assert_eq!(
+            rep("(map list [1 2 3] [:a :b])"),
+            rep("(list (list 1 :a) (list 2 :b))")
+        );
+        assert_eq!(rep("(map + [1 2] [10 20] [100])"), rep("(list 111)"));
+        assert_eq!(rep("(map + [1 2] [])"), rep("(list)"));
+        assert_eq!(rep("(map (fn* (x) (* x 2)) [1 2 3])"), rep("(list 2 4 6)"));
+    }
+
+    #[test]
+    fn test_nth_out_of_range() {
+        assert_eq!(
+            rep("(nth [1 2 3] -1)"),
+            Err(EvalError::IndexOutOfRange { index: -1, len: 3 })
+        );
+        assert_eq!(
+            rep("(nth (list 1 2 3) 3)"),
+            Err(EvalError::IndexOutOfRange { index: 3, len: 3 })
+        );
+    }
+
+    #[test]
+    fn test_keyword_and_string_keys_are_distinct() {
+        let env = default_env();
+        let rep = |input| eval(read_str(input).unwrap(), &env);
+        rep("(def! m (assoc (hash-map :a 1) \"a\" 2))").unwrap();
+        assert_eq!(rep("(count (list (get m :a) (get m \"a\")))"), rep("2"));
+        assert_eq!(rep("(get m :a)"), Ok(Value::Atom(Atom::Int(1))));
+        assert_eq!(rep("(get m \"a\")"), Ok(Value::Atom(Atom::Int(2))));
+        assert_eq!(rep("(get m 'a)"), Ok(Value::Atom(Atom::Nil)));
+        assert_eq!(
+            rep("(get (assoc m :a 3) \"a\")"),
+            Ok(Value::Atom(Atom::Int(2)))
+        );
+        assert_eq!(rep("(get m :a)"), Ok(Value::Atom(Atom::Int(1))));
+    }
+
+    #[test]
+    fn test_maps_keep_insertion_order() {
+        assert_eq!(
+            rep("(pr-str (assoc (hash-map) :z 1 :a 2 :m 3))"),
+            Ok(Value::from("{:z 1 :a 2 :m 3}"))
+        );
+        assert_eq!(
+            rep("(pr-str (assoc {:z 1 :a 2} :z 3))"),
+            Ok(Value::from("{:z 3 :a 2}"))
+        );
+        assert_eq!(
+            rep("(pr-str (dissoc {:z 1 :a 2 :m 3} :a :missing))"),
+            Ok(Value::from("{:z 1 :m 3}"))
+        );
+        assert_eq!(
+            rep("(= {:z 1 :a 2} (hash-map :a 2 :z 1))"),
+            Ok(Value::Atom(Atom::True))
+        );
+        assert_eq!(
+            rep("(= {:z 1 :a 2} {:z 1 :a 3})"),
+            Ok(Value::Atom(Atom::False))
+        );
+    }
+
+    #[test]
+    fn test_keys_and_vals_zip() {
+        let env = default_env();
+        let rep = |input| eval(read_str(input).unwrap(), &env);
+        rep("(def! m (dissoc {:z 1 \"a\" [2] 3 nil :b true} 3))").unwrap();
+        assert_eq!(rep("(keys m)"), Ok(read_str("(:z \"a\" :b)").unwrap()));
+        assert_eq!(rep("(vals m)"), Ok(read_str("(1 [2] true)").unwrap()));
+
+        let (Ok(Value::List(keys)), Ok(Value::List(vals))) = (rep("(keys m)"), rep("(vals m)"))
+        else {
+            panic!("keys and vals should return lists");
+        };
+        let zipped: Map = keys
+            .iter()
+            .zip(vals.iter())
+            .map(|(k, v)| match k {
+                Value::Atom(atom) => (atom.clone(), v.clone()),
+                other => panic!("unexpected key {other:?}"),
+            })
+            .collect();
+        assert_eq!(rep("m"), Ok(Value::HashMap(zipped)));
+
+        assert_eq!(rep("(keys {})"), Ok(Value::list(vec![])));
+        assert!(rep("(vals [])").is_err());
+    }
+
+    #[test]
+    fn test_hash_map_errors() {
+        assert_eq!(rep("(get nil :a)"), Ok(Value::Atom(Atom::Nil)));
+        assert_eq!(rep("(hash-map :a)"), Err(EvalError::UnevenKeyValues));
+        assert_eq!(rep("(assoc {} :a 1 :b)"), Err(EvalError::UnevenKeyValues));
+        assert_eq!(
+            EvalError::UnevenKeyValues.to_string(),
+            "expected an even number of keys and values"
+        );
+        assert_eq!(
+            rep("(assoc (hash-map) (list 1) 2)"),
+            Err(EvalError::UnhashableType(read_str("(1)").unwrap()))
+        );
+        assert_eq!(
+            rep("(hash-map [1] 2)"),
+            Err(EvalError::UnhashableType(read_str("[1]").unwrap()))
+        );
+        assert_eq!(
+            rep("(try* (assoc {} '(1) 2) (catch* e e))"),
+            Ok(Value::from("unhashable type list as hashmap key"))
+        );
+        assert!(rep("(dissoc {} [1])").is_err());
+        assert!(rep("(dissoc [] :a)").is_err());
+    }
+
+    #[test]
+    fn test_atom_keys_agree_with_reader() {
+        assert_eq!(rep("(get (assoc {} nil 1) nil)"), Ok(Value::from(1)));
+        assert_eq!(rep("(get (assoc {} true 2) true)"), Ok(Value::from(2)));
+        assert_eq!(rep("(get (hash-map false 3) false)"), Ok(Value::from(3)));
+        assert_eq!(rep("(get {true 2} false)"), Ok(Value::Atom(Atom::Nil)));
+        assert_eq!(
+            rep("(= {nil 1 true 2 false 3} (assoc {} nil 1 true 2 false 3))"),
+            Ok(Value::Atom(Atom::True))
+        );
+        assert_eq!(rep("(keys {nil 1})"), rep("(list nil)"));
+    }
+
+    #[test]
+    fn test_sleep_elapses() {
+        let env = default_env();
+        let rep = |input| eval(read_str(input).unwrap(), &env);
+        rep("(def! start (time-ms))").unwrap();
+        assert_eq!(rep("(sleep 20)"), Ok(Value::Atom(Atom::Nil)));
+        assert_eq!(
+            rep("(>= (- (time-ms) start) 20)"),
+            Ok(Value::Atom(Atom::True))
+        );
+    }
+
+    #[test]
+    fn test_sleep_errors() {
+        assert!(rep("(sleep -1)").is_err());
+        assert!(rep("(sleep \"10\")").is_err());
+        assert!(rep("(sleep)").is_err());
+    }
+
+    #[test]
+    fn test_reduce() {
+        assert_eq!(rep("(reduce + 10 [1 2 3])"), Ok(Value::Atom(Atom::Int(16))));
+        assert_eq!(
+            rep("(reduce (fn* (acc x) (cons x acc)) () (list 1 2 3))"),
+            rep("(list 3 2 1)")
+        );
+        assert_eq!(
+            rep("(reduce - (list 10 1 2))"),
+            Ok(Value::Atom(Atom::Int(7)))
+        );
+        assert_eq!(rep("(reduce + 5 [])"), Ok(Value::Atom(Atom::Int(5))));
+        assert_eq!(rep("(reduce + [])"), Err(EvalError::EmptyReduce));
+    }
+
+    #[test]
+    fn test_map() {
+        assert_eq!(rep("(map (fn* (x) (* x x)) [1 2 3])"), rep("(list 1 4 9)"));
+        assert_eq!(rep("(map not ())"), rep("(list)"));
+    }
+
+    #[test]
+    fn test_filter_and_remove() {
+        let env = default_env();
+        let rep = |input| eval(read_str(input).unwrap(), &env);
+        rep("(def! even? (fn* (n) (= 0 (- n (* 2 (/ n 2))))))").unwrap();
+        rep("(def! concat-counts (fn* (a b) (+ (count a) (count b))))").unwrap();
+        assert_eq!(
+            rep("(filter even? (list 1 2 3 4 5 6))"),
+            rep("(list 2 4 6)")
+        );
+        assert_eq!(rep("(remove even? [1 2 3 4 5 6])"), rep("(list 1 3 5)"));
+        assert_eq!(
+            rep("(filter (fn* (x) x) [1 nil false 0])"),
+            rep("(list 1 0)")
+        );
+        assert_eq!(rep("(list? (filter even? [2]))"), rep("true"));
+
+        assert_eq!(
+            rep("(concat-counts (filter even? [1 2 3 4 5]) (remove even? [1 2 3 4 5]))"),
+            Ok(Value::Atom(Atom::Int(5)))
+        );
+    }
+
+    #[test]
+    fn test_atoms() {
+        let env = default_env();
+        let rep = |input| eval(read_str(input).unwrap(), &env);
+        rep("(def! a (atom 1))").unwrap();
+        assert_eq!(rep("(atom? a)"), Ok(Value::Atom(Atom::True)));
+        assert_eq!(rep("(atom? 1)"), Ok(Value::Atom(Atom::False)));
+        assert_eq!(rep("@a"), Ok(Value::Atom(Atom::Int(1))));
+        assert_eq!(rep("(reset! a [2])"), rep("[2]"));
+        assert_eq!(rep("(deref a)"), rep("[2]"));
+        assert!(rep("(deref 1)").is_err());
+    }
+
+    #[test]
+    fn test_swap() {
+        let env = default_env();
+        let rep = |input| eval(read_str(input).unwrap(), &env);
+        rep("(def! a (atom 1))").unwrap();
+        assert_eq!(rep("(swap! a + 10 100)"), Ok(Value::Atom(Atom::Int(111))));
+        assert_eq!(
+            rep("(swap! a (fn* (x) (+ x @a)))"),
+            Ok(Value::Atom(Atom::Int(222)))
+        );
+        assert_eq!(
+            rep("(swap! a (fn* (x) (do (reset! a 0) (+ x 1))))"),
+            Ok(Value::Atom(Atom::Int(223)))
+        );
+        assert_eq!(rep("@a"), Ok(Value::Atom(Atom::Int(223))));
+    }
+
+    #[test]
+    fn test_or_and() {
+        assert_eq!(rep("(or)"), Ok(Value::Atom(Atom::Nil)));
+        assert_eq!(
+            rep("(or nil false 3 undefined)"),
+            Ok(Value::Atom(Atom::Int(3)))
+        );
+        assert_eq!(rep("(or nil false)"), Ok(Value::Atom(Atom::False)));
+        assert_eq!(rep("(and)"), Ok(Value::Atom(Atom::True)));
+        assert_eq!(rep("(and 1 2 3)"), Ok(Value::Atom(Atom::Int(3))));
+        assert_eq!(rep("(and 1 nil undefined)"), Ok(Value::Atom(Atom::Nil)));
+        assert_eq!(
+            rep("(let* (condvar 5) (or false condvar))"),
+            Ok(Value::Atom(Atom::Int(5)))
+        );
+    }
+}
@@ -0,0 +1,336 @@
+//! Environments mapping symbols to values.
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt::{self, Debug, Formatter},
+    path::PathBuf,
+    rc::Rc,
+};
+
+use crate::{
+    symbol::Symbol,
+    types::{Function, NativeFn, Value},
+};
+
+#[derive(Default)]
+struct Scope {
+    data: HashMap<Symbol, Value>,
+    outer: Option<Env>,
+    /// The directory of the file being loaded, only used in the root scope.
+    file_dir: Option<PathBuf>,
+    /// The symbols hidden from the outer environments, if this scope is a
+    /// sandbox created with [Env::child_sandbox].
+    denied: Option<HashSet<Symbol>>,
+}
+
+/// A shared, mutable set of symbol bindings with an optional outer
+/// environment.
+///
+/// Cloning an `Env` is cheap and yields another handle to the same bindings.
+#[derive(Clone, Default)]
+pub struct Env(Rc<RefCell<Scope>>);
+
+impl Env {
+    /// Create a new environment with no bindings and no outer environment.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new, empty environment enclosed by `outer`.
+    ///
+    /// Lookups that fail in the new environment fall through to `outer`.
+    pub fn with_outer(outer: &Env) -> Self {
+        Self(Rc::new(RefCell::new(Scope {
+            data: HashMap::new(),
+            outer: Some(outer.clone()),
+            file_dir: None,
+            denied: None,
+        })))
+    }
+
+    /// Create a new, empty sandbox environment enclosed by `outer`.
+    ///
+    /// The sandbox acts as the root of its own chain: `def!` at the top level,
+    /// `eval`, and `load-file` all bind in the sandbox rather than in `outer`.
+    /// The symbols in `denied` cannot be reached from `outer`, and evaluating
+    /// one raises [EvalError::Denied](crate::eval::EvalError::Denied) unless
+    /// the sandbox binds it itself.
+    ///
+    /// Functions defined outside the sandbox keep their own environment, so
+    /// any denied builtins they call still work.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mal::{core::default_env, rep};
+    ///
+    /// let env = default_env();
+    /// let sandbox = env.child_sandbox(&["slurp", "load-file"]);
+    /// assert_eq!(rep("(def! x (+ 1 2))", &sandbox).unwrap(), "3");
+    /// assert!(rep("(slurp \"secrets.txt\")", &sandbox).is_err());
+    /// assert!(env.get("x").is_none());
+    /// ```
+    pub fn child_sandbox(&self, denied: &[&str]) -> Self {
+        Self(Rc::new(RefCell::new(Scope {
+            data: HashMap::new(),
+            outer: Some(self.clone()),
+            file_dir: None,
+            denied: Some(denied.iter().map(|&name| Symbol::from(name)).collect()),
+        })))
+    }
+
+    /// Bind `key` to `value` in this environment, replacing any existing
+    /// binding.
+    pub fn set(&self, key: impl Into<Symbol>, value: Value) {
+        self.0.borrow_mut().data.insert(key.into(), value);
+    }
+
+    /// Bind `name` to the native function `func` in this environment.
+    ///
+    /// This lets programs embedding mal expose their own functions to it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mal::{core::default_env, env::Env, eval::EvalError, rep, types::Value};
+    ///
+    /// fn answer(_args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    ///     Ok(Value::from(42))
+    /// }
+    ///
+    /// let env = default_env();
+    /// env.register("answer", answer);
+    /// assert_eq!(rep("(answer)", &env).unwrap(), "42");
+    /// ```
+    pub fn register(&self, name: &'static str, func: NativeFn) {
+        let func = Function::Native {
+            name,
+            func,
+            meta: None,
+        };
+        self.set(name, Value::Function(func));
+    }
+
+    /// Look up `key` in this environment and its outer environments.
+    ///
+    /// Returns `None` if the symbol is not bound anywhere in the chain, or is
+    /// only bound outside a sandbox that denies it.
+    pub fn get(&self, key: impl Into<Symbol>) -> Option<Value> {
+        let key = key.into();
+        let scope = self.0.borrow();
+        match scope.data.get(&key) {
+            Some(value) => Some(value.clone()),
+            None if scope.is_denied(&key) => None,
+            None => scope.outer.as_ref().and_then(|outer| outer.get(key)),
+        }
+    }
+
+    /// Look up `key` in this environment only, ignoring outer environments.
+    pub(crate) fn get_local(&self, key: impl Into<Symbol>) -> Option<Value> {
+        self.0.borrow().data.get(&key.into()).cloned()
+    }
+
+    /// Whether this environment has no outer environment.
+    pub(crate) fn is_root(&self) -> bool {
+        self.0.borrow().outer.is_none()
+    }
+
+    /// Whether looking up `key` stops at a sandbox that denies it before
+    /// finding a binding.
+    pub fn is_denied(&self, key: impl Into<Symbol>) -> bool {
+        let key = key.into();
+        let scope = self.0.borrow();
+        if scope.data.contains_key(&key) {
+            return false;
+        }
+        scope.is_denied(&key)
+            || scope
+                .outer
+                .as_ref()
+                .is_some_and(|outer| outer.is_denied(key))
+    }
+
+    /// Get the names of all symbols bound in this environment and its outer
+    /// environments, sorted and without duplicates.
+    ///
+    /// Symbols denied by a sandbox are left out unless the sandbox or one of
+    /// its inner environments binds them.
+    pub fn symbols(&self) -> Vec<String> {
+        let mut symbols = vec![];
+        let mut denied = HashSet::new();
+        let mut env = Some(self.clone());
+        while let Some(current) = env {
+            let scope = current.0.borrow();
+            symbols.extend(
+                scope
+                    .data
+                    .keys()
+                    .filter(|key| !denied.contains(*key))
+                    .map(Symbol::to_string),
+            );
+            denied.extend(scope.denied.iter().flatten().copied());
+            env = scope.outer.clone();
+        }
+        symbols.sort();
+        symbols.dedup();
+        symbols
+    }
+
+    /// Get the outermost environment in the chain, or the innermost sandbox
+    /// if there is one.
+    ///
+    /// This is the environment holding the built-in functions and any
+    /// top-level definitions.
+    pub fn root(&self) -> Env {
+        let scope = self.0.borrow();
+        match &scope.outer {
+            Some(outer) if scope.denied.is_none() => outer.root(),
+            _ => self.clone(),
+        }
+    }
+
+    /// The directory of the file currently being loaded with `load-file`, if
+    /// any.
+    ///
+    /// Relative paths given to `slurp` and `load-file` are resolved against
+    /// this directory, or against the working directory if it is `None`.
+    pub fn file_dir(&self) -> Option<PathBuf> {
+        self.root().0.borrow().file_dir.clone()
+    }
+
+    /// Set the directory returned by [Env::file_dir] for the whole chain,
+    /// returning the previous one so that it can be restored.
+    pub fn set_file_dir(&self, dir: Option<PathBuf>) -> Option<PathBuf> {
+        std::mem::replace(&mut self.root().0.borrow_mut().file_dir, dir)
+    }
+}
+
+impl Scope {
+    fn is_denied(&self, key: &Symbol) -> bool {
+        self.denied
+            .as_ref()
+            .is_some_and(|denied| denied.contains(key))
+    }
+}
+
+impl Debug for Env {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Env")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Env;
+    use crate::{
+        core::default_env,
+        eval::EvalError,
+        rep,
+        types::{Atom, Value},
+        MalError,
+    };
+
+    #[test]
+    fn test_get_falls_through_to_outer() {
+        let outer = Env::new();
+        outer.set("a", Value::Atom(Atom::Int(1)));
+        let inner = Env::with_outer(&outer);
+        inner.set("b", Value::Atom(Atom::Int(2)));
+
+        assert_eq!(inner.get("a"), Some(Value::Atom(Atom::Int(1))));
+        assert_eq!(inner.get("b"), Some(Value::Atom(Atom::Int(2))));
+        assert_eq!(outer.get("b"), None);
+    }
+
+    #[test]
+    fn test_set_shadows_outer() {
+        let outer = Env::new();
+        outer.set("a", Value::Atom(Atom::Int(1)));
+        let inner = Env::with_outer(&outer);
+        inner.set("a", Value::Atom(Atom::Int(2)));
+
+        assert_eq!(inner.get("a"), Some(Value::Atom(Atom::Int(2))));
+        assert_eq!(outer.get("a"), Some(Value::Atom(Atom::Int(1))));
+    }
+
+    #[test]
+    fn test_root() {
+        let root = Env::new();
+        let inner = Env::with_outer(&Env::with_outer(&root));
+        inner.root().set("a", Value::Atom(Atom::Int(1)));
+
+        assert_eq!(root.get("a"), Some(Value::Atom(Atom::Int(1))));
+    }
+
+    #[test]
+    fn test_file_dir_is_shared_with_root() {
+        let root = Env::new();
+        let inner = Env::with_outer(&root);
+        assert_eq!(inner.file_dir(), None);
+
+        assert_eq!(inner.set_file_dir(Some("scripts".into())), None);
+        assert_eq!(root.file_dir(), Some("scripts".into()));
+        assert_eq!(root.set_file_dir(None), Some("scripts".into()));
+        assert_eq!(inner.file_dir(), None);
+    }
+
+    #[test]
+    fn test_symbols() {
+        let outer = Env::new();
+        outer.set("b", Value::Atom(Atom::Int(1)));
+        outer.set("a", Value::Atom(Atom::Int(1)));
+        let inner = Env::with_outer(&outer);
+        inner.set("b", Value::Atom(Atom::Int(2)));
+        inner.set("c", Value::Atom(Atom::Int(3)));
+
+        assert_eq!(inner.symbols(), vec!["a", "b", "c"]);
+        assert_eq!(outer.symbols(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_sandbox_denies_symbols() {
+        let env = default_env();
+        let sandbox = env.child_sandbox(&["slurp", "load-file"]);
+
+        assert_eq!(rep("(+ 1 (* 2 3))", &sandbox).unwrap(), "7");
+        assert_eq!(
+            rep("(slurp \"Cargo.toml\")", &sandbox),
+            Err(MalError::Eval(EvalError::Denied("slurp".to_owned())))
+        );
+        assert_eq!(
+            rep("(try* (load-file \"x.mal\") (catch* e e))", &sandbox).unwrap(),
+            "\"'load-file' is not available in this sandbox\""
+        );
+        assert_eq!(rep("(bound? 'slurp)", &sandbox).unwrap(), "false");
+        assert!(!sandbox.symbols().contains(&"slurp".to_owned()));
+        assert!(sandbox.symbols().contains(&"+".to_owned()));
+        assert!(sandbox.is_denied("slurp"));
+        assert!(!env.is_denied("slurp"));
+        assert!(env.get("slurp").is_some());
+    }
+
+    #[test]
+    fn test_sandbox_can_rebind_denied_symbols() {
+        let sandbox = default_env().child_sandbox(&["slurp"]);
+        sandbox.set("slurp", Value::from(1));
+
+        assert_eq!(sandbox.get("slurp"), Some(Value::from(1)));
+        assert!(!sandbox.is_denied("slurp"));
+        assert!(sandbox.symbols().contains(&"slurp".to_owned()));
+    }
+
+    #[test]
+    fn test_sandbox_definitions_stay_local() {
+        let env = default_env();
+        let sandbox = env.child_sandbox(&[]);
+
+        rep("(def! x 1)", &sandbox).unwrap();
+        rep("(eval '(def! y 2))", &sandbox).unwrap();
+
+        assert_eq!(sandbox.get("x"), Some(Value::from(1)));
+        assert_eq!(sandbox.get("y"), Some(Value::from(2)));
+        assert_eq!(env.get("x"), None);
+        assert_eq!(env.get("y"), None);
+        assert_eq!(sandbox.root().get("x"), Some(Value::from(1)));
+    }
+}
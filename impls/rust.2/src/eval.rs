@@ -0,0 +1,993 @@
+//! Evaluation of syntax trees.
+use std::{
+    cell::Cell,
+    fmt::{self, Display, Formatter},
+    rc::Rc,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{
+    core,
+    env::Env,
+    map::Map,
+    printer::pr_str,
+    reader::ReadError,
+    symbol::Symbol,
+    types::{is_truthy, Atom, Closure, Function, Value},
+};
+
+/// Errors that can be raised while evaluating.
+#[derive(Debug, PartialEq, Eq)]
+pub enum EvalError {
+    /// A symbol was evaluated that is not bound in the environment.
+    UnboundSymbol(String),
+    /// A symbol was evaluated that a sandbox created with
+    /// [Env::child_sandbox] denies.
+    Denied(String),
+    /// The first element of a list evaluated to something other than a
+    /// function.
+    NotCallable(Value),
+    /// A macro was passed to `apply` or another function that calls its
+    /// argument, rather than being expanded.
+    ApplyMacro,
+    /// A function or special form was called with the wrong number of
+    /// arguments.
+    WrongArity { expected: String, got: usize },
+    /// A function or special form was given a value of the wrong type.
+    TypeMismatch { expected: &'static str, got: String },
+    /// A `fn*` parameter list had something other than exactly one symbol after
+    /// `&`.
+    MalformedRestParam,
+    /// A `let*` form was given an odd number of elements in its bindings.
+    UnevenBindings,
+    /// `hash-map` or `assoc` was given a key without a value.
+    UnevenKeyValues,
+    /// A value other than an atom was used as a hash-map key.
+    UnhashableType(Value),
+    /// An integer was divided by zero.
+    DivisionByZero,
+    /// The result of an integer operation did not fit in 64 bits.
+    IntegerOverflow,
+    /// `reduce` was given an empty sequence and no initial value.
+    EmptyReduce,
+    /// `range` was given a step of zero.
+    ZeroStep,
+    /// A sequence was indexed with a negative index or one past its end.
+    IndexOutOfRange { index: i64, len: usize },
+    /// A file could not be read.
+    Io { path: String, message: String },
+    /// The contents of a file could not be read as mal source code.
+    Read { path: String, error: ReadError },
+    /// A string passed to `read-string` could not be read as mal source code.
+    Syntax(ReadError),
+    /// A `format` template had a brace that was not part of `{}`, `{{`, or
+    /// `}}`.
+    InvalidFormat(String),
+    /// A value was raised with `throw` and not caught.
+    Thrown(Value),
+    /// Calls to [eval] were nested more deeply than the limit set with
+    /// [set_max_depth].
+    RecursionLimit,
+    /// Evaluation was stopped by [interrupt]. This cannot be caught by
+    /// `try*`.
+    Interrupted,
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            EvalError::UnboundSymbol(sym) => write!(f, "'{sym}' not found"),
+            EvalError::Denied(sym) => write!(f, "'{sym}' is not available in this sandbox"),
+            EvalError::NotCallable(value) => {
+                write!(f, "value of type {} is not callable", value.type_name())
+            }
+            EvalError::ApplyMacro => write!(f, "cannot apply a macro"),
+            EvalError::WrongArity { expected, got } => {
+                write!(
+                    f,
+                    "wrong number of arguments: expected {expected}, got {got}"
+                )
+            }
+            EvalError::TypeMismatch { expected, got } => {
+                write!(f, "type mismatch: expected {expected}, got {got}")
+            }
+            EvalError::MalformedRestParam => {
+                write!(f, "exactly one parameter must follow '&' in fn* parameters")
+            }
+            EvalError::UnevenBindings => write!(f, "odd number of elements in bindings"),
+            EvalError::UnevenKeyValues => {
+                write!(f, "expected an even number of keys and values")
+            }
+            EvalError::UnhashableType(value) => {
+                write!(f, "unhashable type {} as hashmap key", value.type_name())
+            }
+            EvalError::DivisionByZero => write!(f, "division by zero"),
+            EvalError::IntegerOverflow => write!(f, "integer overflow"),
+            EvalError::EmptyReduce => {
+                write!(f, "reduce of empty sequence with no initial value")
+            }
+            EvalError::ZeroStep => write!(f, "range step must not be zero"),
+            EvalError::IndexOutOfRange { index, len } => {
+                write!(f, "index {index} out of range for length {len}")
+            }
+            EvalError::Io { path, message } => write!(f, "failed to read {path}: {message}"),
+            EvalError::Read { path, error } => write!(f, "{path}: {error}"),
+            EvalError::Syntax(error) => write!(f, "{error}"),
+            EvalError::InvalidFormat(reason) => write!(f, "invalid format string: {reason}"),
+            EvalError::Thrown(value) => write!(f, "uncaught exception: {}", pr_str(value, false)),
+            EvalError::RecursionLimit => write!(f, "recursion limit exceeded"),
+            EvalError::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
+
+impl EvalError {
+    /// Convert this error into the value bound by `catch*`.
+    ///
+    /// A value raised with `throw` is returned unchanged. Any other error
+    /// becomes a string describing it.
+    pub fn into_value(self) -> Value {
+        match self {
+            EvalError::Thrown(value) => value,
+            other => Value::from(other.to_string()),
+        }
+    }
+}
+
+/// Symbols that are evaluated as special forms when they appear at the head
+/// of a list.
+pub const SPECIAL_FORMS: &[&str] = &[
+    "def!",
+    "defmacro!",
+    "let*",
+    "do",
+    "if",
+    "fn*",
+    "quote",
+    "quasiquote",
+    "try*",
+    "macroexpand",
+    "macroexpand-1",
+    "macroexpand-all",
+];
+
+thread_local! {
+    static SPECIAL_FORM_SYMBOLS: Vec<(Symbol, &'static str)> = SPECIAL_FORMS
+        .iter()
+        .map(|form| (Symbol::new(form), *form))
+        .collect();
+}
+
+thread_local! {
+    static TRACING: Cell<bool> = const { Cell::new(false) };
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+    static WARN_ON_SHADOW: Cell<bool> = const { Cell::new(true) };
+    static MAX_DEPTH: Cell<usize> = const { Cell::new(DEFAULT_MAX_DEPTH) };
+}
+
+/// The default limit on how deeply calls to [eval] can nest.
+pub const DEFAULT_MAX_DEPTH: usize = 10_000;
+
+/// Set how deeply calls to [eval] can nest on the current thread before
+/// evaluation fails with [EvalError::RecursionLimit].
+///
+/// Each level of nesting uses some of the thread's stack, so the limit should
+/// be low enough for the stack to hold. Deep recursion that is not in tail
+/// position then raises an error that `try*` can catch, rather than
+/// overflowing the stack and aborting the process.
+pub fn set_max_depth(depth: usize) {
+    MAX_DEPTH.with(|max| max.set(depth));
+}
+
+/// Enable or disable tracing on the current thread.
+///
+/// While tracing, each form [eval] is about to evaluate is printed to stderr,
+/// indented by how deeply nested the call to `eval` is.
+pub fn set_tracing(enabled: bool) {
+    TRACING.with(|tracing| tracing.set(enabled));
+}
+
+/// Enable or disable the warning `def!` prints to stderr when it rebinds a
+/// builtin function, such as `(def! + 5)`, on the current thread. The warning
+/// is enabled by default.
+pub fn set_warn_on_shadow(enabled: bool) {
+    WARN_ON_SHADOW.with(|warn| warn.set(enabled));
+}
+
+/// Set by [interrupt] and consumed by the next step of the eval loop.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Stop the evaluation currently in progress on any thread, which then fails
+/// with [EvalError::Interrupted].
+///
+/// This only stores to an atomic flag, so it is safe to call from a signal
+/// handler. The flag is cleared when an evaluation notices it, or by
+/// [clear_interrupt].
+pub fn interrupt() {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+/// Forget any [interrupt] that no evaluation has noticed yet, so that it does
+/// not stop the next one.
+pub fn clear_interrupt() {
+    INTERRUPTED.store(false, Ordering::Relaxed);
+}
+
+/// Tracks the nesting depth of [eval] for the lifetime of one call.
+struct Depth(usize);
+
+impl Depth {
+    fn enter() -> Self {
+        Depth(DEPTH.with(|depth| {
+            depth.set(depth.get() + 1);
+            depth.get()
+        }))
+    }
+
+    fn trace(&self, ast: &Value) {
+        if TRACING.with(Cell::get) {
+            eprintln!("{}{}", "  ".repeat(self.0 - 1), pr_str(ast, false));
+        }
+    }
+}
+
+impl Drop for Depth {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+fn special_form(sym: Symbol) -> Option<&'static str> {
+    SPECIAL_FORM_SYMBOLS.with(|forms| {
+        forms
+            .iter()
+            .find(|(special, _)| *special == sym)
+            .map(|(_, form)| *form)
+    })
+}
+
+pub(crate) fn check_arity(args: &[Value], min: usize, max: usize) -> Result<(), EvalError> {
+    if args.len() < min || args.len() > max {
+        let expected = if min == max {
+            min.to_string()
+        } else {
+            format!("{min} to {max}")
+        };
+        return Err(EvalError::WrongArity {
+            expected,
+            got: args.len(),
+        });
+    }
+    Ok(())
+}
+
+pub(crate) fn expect_symbol(value: &Value) -> Result<Symbol, EvalError> {
+    match value {
+        Value::Atom(Atom::Symbol(sym)) => Ok(*sym),
+        other => Err(EvalError::TypeMismatch {
+            expected: "symbol",
+            got: other.type_name(),
+        }),
+    }
+}
+
+/// Returns the items of `value` if it is a sequence, meaning a list or a
+/// vector.
+pub(crate) fn expect_sequence(value: &Value) -> Result<&[Value], EvalError> {
+    value.as_seq().ok_or_else(|| EvalError::TypeMismatch {
+        expected: "sequence",
+        got: value.type_name(),
+    })
+}
+
+fn eval_ast(ast: Value, env: &Env) -> Result<Value, EvalError> {
+    match ast {
+        Value::Atom(Atom::Symbol(sym)) => env.get(sym).ok_or_else(|| {
+            if env.is_denied(sym) {
+                EvalError::Denied(sym.to_string())
+            } else {
+                EvalError::UnboundSymbol(sym.to_string())
+            }
+        }),
+        Value::List(items) => eval_items(&items, env).map(Value::list),
+        Value::Vector(items) => eval_items(&items, env).map(Value::vector),
+        Value::HashMap(map) => map
+            .into_iter()
+            .map(|(k, v)| Ok((k, eval(v, env)?)))
+            .collect::<Result<Map, _>>()
+            .map(Value::HashMap),
+        other => Ok(other),
+    }
+}
+
+fn eval_items(items: &[Value], env: &Env) -> Result<Vec<Value>, EvalError> {
+    items.iter().map(|item| eval(item.clone(), env)).collect()
+}
+
+/// Create a closure from the parameter list and body of a `fn*` form.
+///
+/// Every parameter must be a symbol, and `&` must be followed by exactly one
+/// more, which collects the remaining arguments.
+fn make_closure(params: &Value, body: Value, env: &Env) -> Result<Value, EvalError> {
+    let ampersand = Symbol::new("&");
+    let mut names = expect_sequence(params)?.iter();
+    let mut params = vec![];
+    let mut rest = None;
+    while let Some(param) = names.next() {
+        match expect_symbol(param)? {
+            name if name == ampersand => {
+                let (Some(name), None) = (names.next(), names.next()) else {
+                    return Err(EvalError::MalformedRestParam);
+                };
+                rest = Some(expect_symbol(name)?);
+                break;
+            }
+            name => params.push(name),
+        }
+    }
+
+    Ok(Value::Function(Function::Closure(Rc::new(Closure {
+        params,
+        rest,
+        body,
+        env: env.clone(),
+        is_macro: false,
+        meta: None,
+    }))))
+}
+
+impl Closure {
+    /// Create the environment for a call to this closure by binding `args` to
+    /// its parameters.
+    fn bind(&self, args: Vec<Value>) -> Result<Env, EvalError> {
+        let arity_ok = match self.rest {
+            Some(_) => args.len() >= self.params.len(),
+            None => args.len() == self.params.len(),
+        };
+        if !arity_ok {
+            let expected = match self.rest {
+                Some(_) => format!("at least {}", self.params.len()),
+                None => self.params.len().to_string(),
+            };
+            return Err(EvalError::WrongArity {
+                expected,
+                got: args.len(),
+            });
+        }
+
+        let env = Env::with_outer(&self.env);
+        let mut args = args.into_iter();
+        for param in &self.params {
+            // arity was checked above, so there is always an argument here.
+            env.set(*param, args.next().unwrap_or(Value::Atom(Atom::Nil)));
+        }
+        if let Some(rest) = &self.rest {
+            env.set(*rest, Value::list(args.collect()));
+        }
+        Ok(env)
+    }
+}
+
+/// Returns the macro called by `ast`, if `ast` is a list whose head is a symbol
+/// bound to a macro.
+fn macro_call(ast: &Value, env: &Env) -> Option<Rc<Closure>> {
+    let Value::List(items) = ast else {
+        return None;
+    };
+    let Some(Value::Atom(Atom::Symbol(sym))) = items.first() else {
+        return None;
+    };
+    match env.get(*sym) {
+        Some(Value::Function(Function::Closure(closure))) if closure.is_macro => Some(closure),
+        _ => None,
+    }
+}
+
+/// Whether binding `name` in `env` would replace one of the functions from
+/// [core::ns](crate::core::ns) in the root environment.
+///
+/// Aliases such as `(def! f +)`, functions added with [Env::register], and
+/// definitions in inner scopes leave the builtin itself in place, so they do
+/// not count.
+fn replaces_builtin(env: &Env, name: Symbol) -> bool {
+    if !env.is_root() {
+        return false;
+    }
+    match env.get_local(name) {
+        Some(Value::Function(Function::Native { name: native, .. })) => {
+            *native == *name.name() && core::ns().iter().any(|(core, _)| *core == native)
+        }
+        _ => false,
+    }
+}
+
+/// Expand `ast` by one level if it is a macro call, or return it unchanged.
+fn macroexpand_1(ast: Value, env: &Env) -> Result<Value, EvalError> {
+    let Some(closure) = macro_call(&ast, env) else {
+        return Ok(ast);
+    };
+    let Value::List(items) = &ast else {
+        unreachable!("macro calls are lists");
+    };
+    let args = items[1..].to_vec();
+    eval(closure.body.clone(), &closure.bind(args)?)
+}
+
+/// Expand `ast` until it is no longer a macro call.
+fn macroexpand(mut ast: Value, env: &Env) -> Result<Value, EvalError> {
+    while macro_call(&ast, env).is_some() {
+        ast = macroexpand_1(ast, env)?;
+    }
+    Ok(ast)
+}
+
+fn is_quote(items: &[Value]) -> bool {
+    match items.first() {
+        Some(Value::Atom(Atom::Symbol(sym))) => special_form(*sym) == Some("quote"),
+        _ => false,
+    }
+}
+
+/// Returns the argument of `ast` if it is a list of the form `(name arg)`.
+fn unquoted<'a>(ast: &'a Value, name: &str) -> Option<&'a Value> {
+    match ast.as_seq() {
+        Some([Value::Atom(Atom::Symbol(sym)), arg])
+            if matches!(ast, Value::List(_)) && *sym == Symbol::new(name) =>
+        {
+            Some(arg)
+        }
+        _ => None,
+    }
+}
+
+/// Evaluate the body of a `quasiquote` form.
+///
+/// `ast` is returned as if quoted, except that `(unquote x)` anywhere inside
+/// it is replaced by the value of `x`, and `(splice-unquote xs)` inside a list
+/// or vector is replaced by the items of the value of `xs`. Lists, vectors,
+/// pairs, and hash-map values are all descended into.
+fn quasiquote(ast: &Value, env: &Env) -> Result<Value, EvalError> {
+    if let Some(arg) = unquoted(ast, "unquote") {
+        return eval(arg.clone(), env);
+    }
+    match ast {
+        Value::List(items) => quasiquote_items(items, env).map(Value::list),
+        Value::Vector(items) => quasiquote_items(items, env).map(Value::vector),
+        Value::Pair(pair) => Ok(Value::Pair(Rc::new((
+            quasiquote(&pair.0, env)?,
+            quasiquote(&pair.1, env)?,
+        )))),
+        Value::HashMap(map) => map
+            .iter()
+            .map(|(k, v)| Ok((k.clone(), quasiquote(v, env)?)))
+            .collect::<Result<Map, _>>()
+            .map(Value::HashMap),
+        other => Ok(other.clone()),
+    }
+}
+
+fn quasiquote_items(items: &[Value], env: &Env) -> Result<Vec<Value>, EvalError> {
+    let mut result = Vec::with_capacity(items.len());
+    for item in items {
+        match unquoted(item, "splice-unquote") {
+            Some(arg) => result.extend_from_slice(expect_sequence(&eval(arg.clone(), env)?)?),
+            None => result.push(quasiquote(item, env)?),
+        }
+    }
+    Ok(result)
+}
+
+/// Returns the symbol and handler of a `(catch* sym handler)` clause.
+fn catch_clause(clause: &Value) -> Result<(Symbol, Value), EvalError> {
+    let catch = Value::Atom(Atom::Symbol(Symbol::new("catch*")));
+    let items = match clause {
+        Value::List(items) if items.first() == Some(&catch) => items,
+        other => {
+            return Err(EvalError::TypeMismatch {
+                expected: "catch* form",
+                got: other.type_name(),
+            })
+        }
+    };
+    check_arity(&items[1..], 2, 2)?;
+    Ok((expect_symbol(&items[1])?, items[2].clone()))
+}
+
+/// Expand `ast` and then every macro call nested anywhere inside the result,
+/// including hash-map values but not within `quote` forms. Rebuilt collections
+/// keep their metadata.
+fn macroexpand_all(ast: Value, env: &Env) -> Result<Value, EvalError> {
+    let expand_items = |items: &[Value]| -> Result<Vec<Value>, EvalError> {
+        items
+            .iter()
+            .map(|item| macroexpand_all(item.clone(), env))
+            .collect()
+    };
+    match macroexpand(ast, env)? {
+        Value::List(items) if is_quote(&items) => Ok(Value::List(items)),
+        Value::List(items) => Ok(Value::List(items.with_items(expand_items(&items)?))),
+        Value::Vector(items) => Ok(Value::Vector(items.with_items(expand_items(&items)?))),
+        Value::HashMap(map) => {
+            let expanded = map
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), macroexpand_all(value.clone(), env)?)))
+                .collect::<Result<Map, EvalError>>()?;
+            Ok(Value::HashMap(expanded.with_meta(map.meta.clone())))
+        }
+        other => Ok(other),
+    }
+}
+
+/// Call `func` with the already-evaluated `args`.
+///
+/// This is used by native functions that need to call back into mal code.
+/// Macros cannot be called this way, since they expect unevaluated forms.
+pub fn apply(func: &Value, args: Vec<Value>, env: &Env) -> Result<Value, EvalError> {
+    match func {
+        Value::Function(Function::Native { func, .. }) => func(&args, env),
+        Value::Function(Function::Closure(closure)) if closure.is_macro => {
+            Err(EvalError::ApplyMacro)
+        }
+        Value::Function(Function::Closure(closure)) => {
+            eval(closure.body.clone(), &closure.bind(args)?)
+        }
+        other => Err(EvalError::NotCallable(other.clone())),
+    }
+}
+
+/// Evaluate a mal value in the given environment.
+///
+/// Symbols are looked up in `env`, lists are treated as special forms or
+/// function calls, and everything else evaluates to itself (with the elements
+/// of vectors and hash-maps evaluated in turn).
+///
+/// # Arguments
+///
+/// * `ast` - The syntax tree to evaluate, usually produced by
+///   [read_str](crate::reader::read_str).
+/// * `env` - The environment to evaluate in.
+///
+/// # Examples
+///
+/// ```
+/// use mal::{core::default_env, eval::eval, reader::read_str, types::{Atom, Value}};
+///
+/// let env = default_env();
+/// let value = eval(read_str("(if true (+ 1 2) 0)").unwrap(), &env).unwrap();
+/// assert_eq!(value, Value::Atom(Atom::Int(3)));
+/// ```
+pub fn eval(ast: Value, env: &Env) -> Result<Value, EvalError> {
+    let mut ast = ast;
+    let mut env = env.clone();
+    let depth = Depth::enter();
+    if depth.0 > MAX_DEPTH.with(Cell::get) {
+        return Err(EvalError::RecursionLimit);
+    }
+
+    loop {
+        depth.trace(&ast);
+        if INTERRUPTED.swap(false, Ordering::Relaxed) {
+            return Err(EvalError::Interrupted);
+        }
+        ast = macroexpand(ast, &env)?;
+        let items = match ast {
+            Value::List(items) if !items.is_empty() => items,
+            other => return eval_ast(other, &env),
+        };
+
+        let special = match &items[0] {
+            Value::Atom(Atom::Symbol(sym)) => special_form(*sym),
+            _ => None,
+        };
+        if let Some(special) = special {
+            let args = &items[1..];
+            match special {
+                "def!" => {
+                    check_arity(args, 2, 2)?;
+                    let name = expect_symbol(&args[0])?;
+                    let value = eval(args[1].clone(), &env)?;
+                    if WARN_ON_SHADOW.with(Cell::get) && replaces_builtin(&env, name) {
+                        eprintln!("warning: redefining builtin '{name}'");
+                    }
+                    env.set(name, value.clone());
+                    return Ok(value);
+                }
+                "defmacro!" => {
+                    check_arity(args, 2, 2)?;
+                    let name = expect_symbol(&args[0])?;
+                    let value = match eval(args[1].clone(), &env)? {
+                        Value::Function(Function::Closure(closure)) => {
+                            Value::Function(Function::Closure(Rc::new(Closure {
+                                is_macro: true,
+                                ..closure.copy()
+                            })))
+                        }
+                        other => {
+                            return Err(EvalError::TypeMismatch {
+                                expected: "fn* closure",
+                                got: other.type_name(),
+                            })
+                        }
+                    };
+                    env.set(name, value.clone());
+                    return Ok(value);
+                }
+                "let*" => {
+                    check_arity(args, 2, 2)?;
+                    let bindings = expect_sequence(&args[0])?;
+                    if bindings.len() % 2 != 0 {
+                        return Err(EvalError::UnevenBindings);
+                    }
+                    let inner = Env::with_outer(&env);
+                    for pair in bindings.chunks(2) {
+                        let name = expect_symbol(&pair[0])?;
+                        let value = eval(pair[1].clone(), &inner)?;
+                        inner.set(name, value);
+                    }
+                    env = inner;
+                    ast = args[1].clone();
+                    continue;
+                }
+                "do" => {
+                    let Some((last, init)) = args.split_last() else {
+                        return Ok(Value::Atom(Atom::Nil));
+                    };
+                    for item in init {
+                        eval(item.clone(), &env)?;
+                    }
+                    ast = last.clone();
+                    continue;
+                }
+                "if" => {
+                    check_arity(args, 2, 3)?;
+                    ast = if is_truthy(&eval(args[0].clone(), &env)?) {
+                        args[1].clone()
+                    } else {
+                        args.get(2).cloned().unwrap_or(Value::Atom(Atom::Nil))
+                    };
+                    continue;
+                }
+                "fn*" => {
+                    check_arity(args, 2, 2)?;
+                    return make_closure(&args[0], args[1].clone(), &env);
+                }
+                "quote" => {
+                    check_arity(args, 1, 1)?;
+                    return Ok(args[0].clone());
+                }
+                "quasiquote" => {
+                    check_arity(args, 1, 1)?;
+                    return quasiquote(&args[0], &env);
+                }
+                "try*" => {
+                    check_arity(args, 1, 2)?;
+                    let Some(clause) = args.get(1) else {
+                        return eval(args[0].clone(), &env);
+                    };
+                    let (name, handler) = catch_clause(clause)?;
+                    match eval(args[0].clone(), &env) {
+                        Ok(value) => return Ok(value),
+                        Err(EvalError::Interrupted) => return Err(EvalError::Interrupted),
+                        Err(error) => {
+                            let inner = Env::with_outer(&env);
+                            inner.set(name, error.into_value());
+                            env = inner;
+                            ast = handler;
+                            continue;
+                        }
+                    }
+                }
+                "macroexpand" => {
+                    check_arity(args, 1, 1)?;
+                    return macroexpand(args[0].clone(), &env);
+                }
+                "macroexpand-1" => {
+                    check_arity(args, 1, 1)?;
+                    return macroexpand_1(args[0].clone(), &env);
+                }
+                "macroexpand-all" => {
+                    check_arity(args, 1, 1)?;
+                    return macroexpand_all(args[0].clone(), &env);
+                }
+                _ => (),
+            }
+        }
+
+        let mut args = eval_items(&items, &env)?;
+        let func = args.remove(0);
+        match func {
+            Value::Function(Function::Native { func, .. }) => return func(&args, &env),
+            Value::Function(Function::Closure(closure)) => {
+                env = closure.bind(args)?;
+                ast = closure.body.clone();
+            }
+            other => return Err(EvalError::NotCallable(other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        eval, macroexpand_all, replaces_builtin, set_max_depth, EvalError, DEFAULT_MAX_DEPTH,
+    };
+    use crate::{
+        core::default_env,
+        env::Env,
+        reader::read_str,
+        types::{Atom, Value},
+    };
+
+    fn rep(input: &str) -> Result<Value, EvalError> {
+        eval(read_str(input).unwrap(), &default_env())
+    }
+
+    #[test]
+    fn test_replaces_builtin() {
+        fn host(_args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+            Ok(Value::Atom(Atom::Nil))
+        }
+
+        let env = default_env();
+        let rep = |input: &str| eval(read_str(input).unwrap(), &env).unwrap();
+        assert!(replaces_builtin(&env, "+".into()));
+        assert!(replaces_builtin(&env, "count".into()));
+        assert!(!replaces_builtin(&env, "undefined-name".into()));
+
+        rep("(def! f +)");
+        assert!(!replaces_builtin(&env, "f".into()));
+
+        env.register("host-fn", host);
+        assert!(!replaces_builtin(&env, "host-fn".into()));
+
+        assert!(!replaces_builtin(&Env::with_outer(&env), "+".into()));
+        assert!(!replaces_builtin(&env.child_sandbox(&[]), "+".into()));
+
+        rep("(def! + 5)");
+        assert!(!replaces_builtin(&env, "+".into()));
+    }
+
+    #[test]
+    fn test_error_display() {
+        assert_eq!(
+            EvalError::UnboundSymbol("x".to_owned()).to_string(),
+            "'x' not found"
+        );
+        assert_eq!(
+            EvalError::WrongArity {
+                expected: "1 to 2".to_owned(),
+                got: 3
+            }
+            .to_string(),
+            "wrong number of arguments: expected 1 to 2, got 3"
+        );
+        assert_eq!(
+            EvalError::TypeMismatch {
+                expected: "int",
+                got: "list".to_owned()
+            }
+            .to_string(),
+            "type mismatch: expected int, got list"
+        );
+        assert_eq!(
+            EvalError::Thrown(Value::from("oops")).to_string(),
+            "uncaught exception: \"oops\""
+        );
+    }
+
+    #[test]
+    fn test_eval_self_evaluating() {
+        assert_eq!(rep("42"), Ok(Value::Atom(Atom::Int(42))));
+        assert_eq!(rep("[1 (+ 1 1)]"), rep("[1 2]"));
+    }
+
+    #[test]
+    fn test_eval_unbound_symbol() {
+        assert_eq!(
+            rep("undefined"),
+            Err(EvalError::UnboundSymbol("undefined".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_eval_if() {
+        assert_eq!(rep("(if 0 1 2)"), Ok(Value::Atom(Atom::Int(1))));
+        assert_eq!(rep("(if nil 1 2)"), Ok(Value::Atom(Atom::Int(2))));
+        assert_eq!(rep("(if false 1)"), Ok(Value::Atom(Atom::Nil)));
+    }
+
+    #[test]
+    fn test_eval_let_and_do() {
+        assert_eq!(
+            rep("(let* (a 1 b (+ a 1)) (do a b))"),
+            Ok(Value::Atom(Atom::Int(2)))
+        );
+        assert_eq!(rep("(let* (a) a)"), Err(EvalError::UnevenBindings));
+    }
+
+    #[test]
+    fn test_eval_closure() {
+        assert_eq!(
+            rep("((fn* (a & more) (list a more)) 1 2 3)"),
+            rep("(list 1 (list 2 3))")
+        );
+        assert_eq!(
+            rep("((fn* (a b) a) 1)"),
+            Err(EvalError::WrongArity {
+                expected: "2".to_owned(),
+                got: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_recursion_limit() {
+        let env = default_env();
+        let rep = |input: &str| eval(read_str(input).unwrap(), &env);
+        rep("(def! f (fn* (n) (if (= n 0) 0 (+ 1 (f (- n 1))))))").unwrap();
+        set_max_depth(100);
+        assert_eq!(rep("(f 50)"), Ok(Value::from(50)));
+        assert_eq!(rep("(f 1000)"), Err(EvalError::RecursionLimit));
+        assert_eq!(
+            rep("(try* (f 1000) (catch* e e))"),
+            Ok(Value::from("recursion limit exceeded"))
+        );
+        // Tail calls do not nest, so they are not limited.
+        rep("(def! g (fn* (n) (if (= n 0) :done (g (- n 1)))))").unwrap();
+        assert_eq!(rep("(g 1000)"), rep(":done"));
+        set_max_depth(DEFAULT_MAX_DEPTH);
+    }
+
+    #[test]
+    fn test_malformed_params() {
+        assert_eq!(
+            rep("(fn* (a b & c d) a)"),
+            Err(EvalError::MalformedRestParam)
+        );
+        assert_eq!(rep("(fn* (a &) a)"), Err(EvalError::MalformedRestParam));
+        assert_eq!(
+            rep("(fn* (a 1) a)"),
+            Err(EvalError::TypeMismatch {
+                expected: "symbol",
+                got: "int".to_owned()
+            })
+        );
+        assert_eq!(
+            rep("(fn* (& [xs]) xs)"),
+            Err(EvalError::TypeMismatch {
+                expected: "symbol",
+                got: "vector".to_owned()
+            })
+        );
+        assert_eq!(rep("((fn* (& xs) xs))"), rep("(list)"));
+    }
+
+    #[test]
+    fn test_eval_tail_call() {
+        let env = default_env();
+        eval(
+            read_str("(def! count-down (fn* (n) (if (= n 0) 0 (count-down (- n 1)))))").unwrap(),
+            &env,
+        )
+        .unwrap();
+        let value = eval(read_str("(count-down 100000)").unwrap(), &env);
+        assert_eq!(value, Ok(Value::Atom(Atom::Int(0))));
+    }
+
+    #[test]
+    fn test_eval_quote() {
+        assert_eq!(rep("(quote (a b))"), Ok(read_str("(a b)").unwrap()));
+        assert_eq!(rep("'sym"), Ok(Value::Atom(Atom::Symbol("sym".into()))));
+    }
+
+    #[test]
+    fn test_eval_quasiquote() {
+        let env = default_env();
+        let rep = |input| eval(read_str(input).unwrap(), &env);
+        rep("(def! x 2)").unwrap();
+        rep("(def! xs (list 3 4))").unwrap();
+        assert_eq!(rep("`(1 ~x)"), Ok(read_str("(1 2)").unwrap()));
+        assert_eq!(rep("`(1 ~@xs 5)"), Ok(read_str("(1 3 4 5)").unwrap()));
+        assert_eq!(rep("`(a (b ~x))"), Ok(read_str("(a (b 2))").unwrap()));
+        assert_eq!(rep("`[1 ~x]"), Ok(read_str("[1 2]").unwrap()));
+        assert_eq!(rep("`[1 ~@xs]"), Ok(read_str("[1 3 4]").unwrap()));
+        assert!(matches!(rep("`[~@xs]"), Ok(Value::Vector(_))));
+        assert_eq!(
+            rep("`{:k ~x :l [~@xs]}"),
+            Ok(read_str("{:k 2 :l [3 4]}").unwrap())
+        );
+        assert_eq!(rep("`(~x . y)"), Ok(read_str("(2 . y)").unwrap()));
+        assert_eq!(rep("`~x"), Ok(Value::Atom(Atom::Int(2))));
+        assert_eq!(rep("`x"), Ok(Value::Atom(Atom::Symbol("x".into()))));
+        assert!(rep("`(~@x)").is_err());
+    }
+
+    #[test]
+    fn test_eval_try_catch() {
+        assert_eq!(
+            rep("(try* (throw \"my error\") (catch* e e))"),
+            Ok(Value::from("my error"))
+        );
+        assert_eq!(
+            rep("(try* (throw {:code 1}) (catch* e (get e :code)))"),
+            Ok(Value::Atom(Atom::Int(1)))
+        );
+        assert_eq!(
+            rep("(try* undefined (catch* e e))"),
+            Ok(Value::from("'undefined' not found"))
+        );
+        assert_eq!(
+            rep("(try* (+ 1 2) (catch* e undefined))"),
+            Ok(Value::Atom(Atom::Int(3)))
+        );
+        assert_eq!(rep("(try* 7)"), Ok(Value::Atom(Atom::Int(7))));
+        assert_eq!(
+            rep("(try* (throw 1) (catch* e (throw (+ e 1))))"),
+            Err(EvalError::Thrown(Value::Atom(Atom::Int(2))))
+        );
+        assert!(rep("(try* 1 (oops e e))").is_err());
+        assert!(rep("(try* 1 (catch* e))").is_err());
+    }
+
+    #[test]
+    fn test_eval_defmacro() {
+        let env = default_env();
+        eval(
+            read_str("(defmacro! unless (fn* (c a b) (list 'if c b a)))").unwrap(),
+            &env,
+        )
+        .unwrap();
+        let value = eval(read_str("(unless false 1 undefined)").unwrap(), &env);
+        assert_eq!(value, Ok(Value::Atom(Atom::Int(1))));
+    }
+
+    #[test]
+    fn test_macroexpand_levels() {
+        let env = default_env();
+        let rep = |input| eval(read_str(input).unwrap(), &env);
+        rep("(defmacro! unless (fn* (c a b) (list 'if c b a)))").unwrap();
+        rep("(defmacro! when-not (fn* (c a) (list 'unless c a nil)))").unwrap();
+
+        assert_eq!(
+            rep("(macroexpand-1 (when-not x (when-not y 1)))"),
+            Ok(read_str("(unless x (when-not y 1) nil)").unwrap())
+        );
+        assert_eq!(
+            rep("(macroexpand (when-not x (when-not y 1)))"),
+            Ok(read_str("(if x nil (when-not y 1))").unwrap())
+        );
+        assert_eq!(
+            rep("(macroexpand-all (when-not x (when-not y 1)))"),
+            Ok(read_str("(if x nil (if y nil 1))").unwrap())
+        );
+        assert_eq!(
+            rep("(macroexpand-all [(when-not x 1) '(when-not y 2)])"),
+            Ok(read_str("[(if x nil 1) (quote (when-not y 2))]").unwrap())
+        );
+        assert_eq!(
+            rep("(macroexpand-all (list [(when-not y 2)] {:k (when-not z 3)}))"),
+            Ok(read_str("(list [(if y nil 2)] {:k (if z nil 3)})").unwrap())
+        );
+        assert_eq!(
+            rep("(macroexpand-all ^{:m 1} [(when-not x 1)])"),
+            Ok(read_str("(with-meta [(if x nil 1)] {:m 1})").unwrap())
+        );
+
+        // Collections that already carry metadata keep it once expanded.
+        let meta = read_str("{:m 1}").unwrap();
+        for input in [
+            "[(when-not x 1)]",
+            "(list (when-not x 1))",
+            "{:k (when-not x 1)}",
+        ] {
+            let form = read_str(input).unwrap().with_meta(meta.clone()).unwrap();
+            let expanded = macroexpand_all(form, &env).unwrap();
+            assert_eq!(expanded.meta(), meta, "{input}");
+            assert!(!format!("{expanded:?}").contains("when-not"), "{input}");
+        }
+        assert_eq!(
+            rep("(macroexpand-1 (+ 1 2))"),
+            Ok(read_str("(+ 1 2)").unwrap())
+        );
+    }
+}
@@ -0,0 +1,562 @@
+//! Conversion between mal values and JSON text without depending on serde.
+//!
+//! Values are encoded as follows:
+//!
+//! * `nil`, `true`, `false`, integers, floats, and strings map to their JSON
+//!   equivalents.
+//! * Lists and vectors map to arrays. Arrays are read back as vectors.
+//! * Hash-maps map to objects. Keys must be strings or keywords, and keywords
+//!   are written without their leading colon, so they are read back as strings.
+//!
+//! Anything else, such as symbols, characters, functions, and non-finite
+//! floats, cannot be converted to JSON. For a lossless encoding of every value,
+//! enable the `serde` feature instead.
+//!
+//! Decoding stops with an error past [MAX_DEPTH] levels of nesting, so that
+//! untrusted input cannot overflow the stack.
+use std::{
+    fmt::{self, Display, Formatter, Write},
+    iter::Peekable,
+    str::CharIndices,
+};
+
+use crate::{
+    map::Map,
+    printer::pr_str,
+    types::{Atom, Float, Value},
+};
+
+/// The deepest nesting of arrays and objects that [Value::from_json] accepts.
+pub const MAX_DEPTH: usize = 128;
+
+/// Errors that can occur when converting values to or from JSON.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonError {
+    /// A character was encountered that cannot appear at this point in the
+    /// input.
+    UnexpectedCharacter(char, usize),
+    /// The end of the input was reached before a complete value was read.
+    UnexpectedEndOfInput(usize),
+    /// A string contained an escape sequence that is not valid JSON.
+    InvalidEscape(usize),
+    /// A number could not be parsed.
+    InvalidNumber(usize),
+    /// An array or object was nested more than [MAX_DEPTH] levels deep. The
+    /// position is that of its opening bracket.
+    TooDeep(usize),
+    /// A hash-map key was not a string or keyword. Holds the readable form of
+    /// the key.
+    UnsupportedKey(String),
+    /// A value of this type has no JSON equivalent.
+    Unsupported(String),
+}
+
+impl JsonError {
+    /// A short identifier for the kind of error, such as `"unexpected-eof"`.
+    ///
+    /// Unlike the [Display] output, codes are stable and can be matched on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            JsonError::UnexpectedCharacter(..) => "unexpected-character",
+            JsonError::UnexpectedEndOfInput(_) => "unexpected-eof",
+            JsonError::InvalidEscape(_) => "invalid-escape",
+            JsonError::InvalidNumber(_) => "invalid-number",
+            JsonError::TooDeep(_) => "too-deep",
+            JsonError::UnsupportedKey(_) => "unsupported-key",
+            JsonError::Unsupported(_) => "unsupported-value",
+        }
+    }
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            JsonError::UnexpectedCharacter(c, pos) => {
+                write!(f, "unexpected character: {c} at position {pos}")
+            }
+            JsonError::UnexpectedEndOfInput(pos) => {
+                write!(f, "unexpected end of input at position {pos}")
+            }
+            JsonError::InvalidEscape(pos) => write!(f, "invalid escape at position {pos}"),
+            JsonError::InvalidNumber(pos) => write!(f, "invalid number at position {pos}"),
+            JsonError::TooDeep(pos) => {
+                write!(
+                    f,
+                    "nesting deeper than {MAX_DEPTH} levels at position {pos}"
+                )
+            }
+            JsonError::UnsupportedKey(key) => {
+                write!(f, "cannot use {key} as a JSON object key")
+            }
+            JsonError::Unsupported(type_name) => {
+                write!(f, "cannot convert {type_name} to JSON")
+            }
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl Value {
+    /// Encode this value as compact JSON text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mal::reader::read_str;
+    ///
+    /// let value = read_str(r#"{:a [1 "two" nil]}"#).unwrap();
+    /// assert_eq!(value.to_json().unwrap(), r#"{"a":[1,"two",null]}"#);
+    /// ```
+    pub fn to_json(&self) -> Result<String, JsonError> {
+        let mut out = String::new();
+        write_value(&mut out, self)?;
+        Ok(out)
+    }
+
+    /// Decode a single JSON value, surrounded by optional whitespace.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mal::{reader::read_str, types::Value};
+    ///
+    /// let value = Value::from_json(r#"{"a": [1, 2.5, true]}"#).unwrap();
+    /// assert_eq!(value, read_str(r#"{"a" [1 2.5 true]}"#).unwrap());
+    /// ```
+    pub fn from_json(input: &str) -> Result<Value, JsonError> {
+        let mut decoder = Decoder {
+            input,
+            chars: input.char_indices().peekable(),
+            depth: 0,
+        };
+        let value = decoder.value()?;
+        decoder.skip_whitespace();
+        match decoder.chars.next() {
+            Some((pos, c)) => Err(JsonError::UnexpectedCharacter(c, pos)),
+            None => Ok(value),
+        }
+    }
+}
+
+fn write_value(out: &mut String, value: &Value) -> Result<(), JsonError> {
+    match value {
+        Value::Atom(Atom::Nil) => out.push_str("null"),
+        Value::Atom(Atom::True) => out.push_str("true"),
+        Value::Atom(Atom::False) => out.push_str("false"),
+        Value::Atom(Atom::Int(int)) => write!(out, "{int}").unwrap(),
+        // Debug formatting always includes a decimal point or exponent, so
+        // the number is read back as a float.
+        Value::Atom(Atom::Float(Float(float))) if float.is_finite() => {
+            write!(out, "{float:?}").unwrap()
+        }
+        Value::Atom(Atom::String(string)) => write_string(out, string),
+        Value::List(items) | Value::Vector(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(out, item)?;
+            }
+            out.push(']');
+        }
+        Value::HashMap(map) => {
+            out.push('{');
+            for (i, (key, value)) in map.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                match key {
+                    Atom::String(name) | Atom::Keyword(name) => write_string(out, name),
+                    key => {
+                        let key = pr_str(&Value::Atom(key.clone()), true);
+                        return Err(JsonError::UnsupportedKey(key));
+                    }
+                }
+                out.push(':');
+                write_value(out, value)?;
+            }
+            out.push('}');
+        }
+        value => return Err(JsonError::Unsupported(value.type_name())),
+    }
+    Ok(())
+}
+
+fn write_string(out: &mut String, string: &str) {
+    out.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+struct Decoder<'a> {
+    input: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    /// The number of arrays and objects currently being decoded.
+    depth: usize,
+}
+
+impl Decoder<'_> {
+    fn skip_whitespace(&mut self) {
+        while self
+            .chars
+            .next_if(|(_, c)| matches!(c, ' ' | '\t' | '\n' | '\r'))
+            .is_some()
+        {}
+    }
+
+    fn next(&mut self) -> Result<(usize, char), JsonError> {
+        self.chars
+            .next()
+            .ok_or(JsonError::UnexpectedEndOfInput(self.input.len()))
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), JsonError> {
+        match self.next()? {
+            (_, c) if c == expected => Ok(()),
+            (pos, c) => Err(JsonError::UnexpectedCharacter(c, pos)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, JsonError> {
+        self.skip_whitespace();
+        let (pos, c) = *self
+            .chars
+            .peek()
+            .ok_or(JsonError::UnexpectedEndOfInput(self.input.len()))?;
+        match c {
+            '[' | '{' => {
+                if self.depth == MAX_DEPTH {
+                    return Err(JsonError::TooDeep(pos));
+                }
+                self.depth += 1;
+                let value = if c == '[' {
+                    self.array()
+                } else {
+                    self.object()
+                };
+                self.depth -= 1;
+                value
+            }
+            '"' => Ok(Value::Atom(Atom::String(self.string()?))),
+            '-' | '0'..='9' => self.number(),
+            'n' => self.literal("null", Value::Atom(Atom::Nil)),
+            't' => self.literal("true", Value::from(true)),
+            'f' => self.literal("false", Value::from(false)),
+            c => Err(JsonError::UnexpectedCharacter(c, pos)),
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Value) -> Result<Value, JsonError> {
+        for expected in word.chars() {
+            self.expect(expected)?;
+        }
+        Ok(value)
+    }
+
+    fn array(&mut self) -> Result<Value, JsonError> {
+        self.expect('[')?;
+        let mut items = vec![];
+        self.skip_whitespace();
+        if self.chars.next_if(|(_, c)| *c == ']').is_some() {
+            return Ok(Value::vector(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.next()? {
+                (_, ',') => (),
+                (_, ']') => return Ok(Value::vector(items)),
+                (pos, c) => return Err(JsonError::UnexpectedCharacter(c, pos)),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, JsonError> {
+        self.expect('{')?;
+        let mut map = Map::new();
+        self.skip_whitespace();
+        if self.chars.next_if(|(_, c)| *c == '}').is_some() {
+            return Ok(Value::HashMap(map));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            let value = self.value()?;
+            map.insert(Atom::String(key), value);
+            self.skip_whitespace();
+            match self.next()? {
+                (_, ',') => (),
+                (_, '}') => return Ok(Value::HashMap(map)),
+                (pos, c) => return Err(JsonError::UnexpectedCharacter(c, pos)),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.next()? {
+                (_, '"') => return Ok(string),
+                (pos, '\\') => string.push(self.escape(pos)?),
+                (pos, c) if c.is_control() => return Err(JsonError::UnexpectedCharacter(c, pos)),
+                (_, c) => string.push(c),
+            }
+        }
+    }
+
+    /// Decode the escape sequence following the backslash at `pos`.
+    fn escape(&mut self, pos: usize) -> Result<char, JsonError> {
+        Ok(match self.next()?.1 {
+            '"' => '"',
+            '\\' => '\\',
+            '/' => '/',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'u' => {
+                let high = self.hex4(pos)?;
+                if !(0xd800..0xdc00).contains(&high) {
+                    return char::from_u32(high).ok_or(JsonError::InvalidEscape(pos));
+                }
+                // A high surrogate must be followed by an escaped low surrogate.
+                self.expect('\\')?;
+                self.expect('u')?;
+                let low = self.hex4(pos)?;
+                if !(0xdc00..0xe000).contains(&low) {
+                    return Err(JsonError::InvalidEscape(pos));
+                }
+                char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))
+                    .ok_or(JsonError::InvalidEscape(pos))?
+            }
+            _ => return Err(JsonError::InvalidEscape(pos)),
+        })
+    }
+
+    fn hex4(&mut self, pos: usize) -> Result<u32, JsonError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self.next()?.1.to_digit(16);
+            code = code * 16 + digit.ok_or(JsonError::InvalidEscape(pos))?;
+        }
+        Ok(code)
+    }
+
+    fn number(&mut self) -> Result<Value, JsonError> {
+        let start = self.chars.peek().map_or(self.input.len(), |(pos, _)| *pos);
+        let mut end = start;
+        let mut is_float = false;
+        while let Some((pos, c)) = self
+            .chars
+            .next_if(|(_, c)| matches!(c, '-' | '+' | '.' | 'e' | 'E' | '0'..='9'))
+        {
+            is_float |= matches!(c, '.' | 'e' | 'E');
+            end = pos + c.len_utf8();
+        }
+        let text = &self.input[start..end];
+        if !is_number(text) {
+            return Err(JsonError::InvalidNumber(start));
+        }
+        if !is_float {
+            if let Ok(int) = text.parse::<i64>() {
+                return Ok(Value::from(int));
+            }
+        }
+        text.parse::<f64>()
+            .map(Value::from)
+            .map_err(|_| JsonError::InvalidNumber(start))
+    }
+}
+
+/// Whether `text` matches the JSON number grammar, which is stricter than
+/// Rust's: no leading zeros, no leading `+`, and digits on both sides of `.`.
+fn is_number(text: &str) -> bool {
+    fn digits(text: &str) -> (&str, &str) {
+        text.split_at(
+            text.find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(text.len()),
+        )
+    }
+
+    let text = text.strip_prefix('-').unwrap_or(text);
+    let (int, mut rest) = digits(text);
+    if int.is_empty() || (int.len() > 1 && int.starts_with('0')) {
+        return false;
+    }
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let (fraction, after) = digits(fraction);
+        if fraction.is_empty() {
+            return false;
+        }
+        rest = after;
+    }
+    if let Some(exponent) = rest.strip_prefix(['e', 'E']) {
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        let (exponent, after) = digits(exponent);
+        if exponent.is_empty() {
+            return false;
+        }
+        rest = after;
+    }
+    rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::read_str;
+
+    fn read(input: &str) -> Value {
+        read_str(input).unwrap()
+    }
+
+    #[test]
+    fn nested_round_trip() {
+        let value = read(
+            r#"{"name" "mal" "tags" ["lisp" "rust"] "meta" {"stars" 42 "ratio" 0.5 "ok" true "missing" nil} "empty" [{} []]}"#,
+        );
+        let json = value.to_json().unwrap();
+        assert_eq!(Value::from_json(&json).unwrap(), value);
+    }
+
+    #[test]
+    fn lists_and_keywords_read_back_as_vectors_and_strings() {
+        let json = read(r#"{:items (1 (2 3)) :nested {:k "v"}}"#)
+            .to_json()
+            .unwrap();
+        assert_eq!(json, r#"{"items":[1,[2,3]],"nested":{"k":"v"}}"#);
+        assert_eq!(
+            Value::from_json(&json).unwrap(),
+            read(r#"{"items" [1 [2 3]] "nested" {"k" "v"}}"#)
+        );
+    }
+
+    #[test]
+    fn strings_are_escaped() {
+        let value = Value::from("quote \" slash \\ line\n tab\t bell\u{7} é");
+        let json = value.to_json().unwrap();
+        assert_eq!(json, r#""quote \" slash \\ line\n tab\t bell\u0007 é""#);
+        assert_eq!(Value::from_json(&json).unwrap(), value);
+    }
+
+    #[test]
+    fn unicode_escapes_are_decoded() {
+        assert_eq!(Value::from_json(r#""é😀\/""#).unwrap(), Value::from("é😀/"));
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(Value::from_json("-12").unwrap(), Value::from(-12));
+        assert_eq!(Value::from_json("1.5e2").unwrap(), Value::from(150.0));
+        assert_eq!(Value::from_json("0.25").unwrap(), Value::from(0.25));
+        assert_eq!(Value::from(1.0).to_json().unwrap(), "1.0");
+        for input in ["01", "-", "1.", "1.2.3", "1e", "2-3"] {
+            assert_eq!(
+                Value::from_json(input),
+                Err(JsonError::InvalidNumber(0)),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn whitespace_is_allowed_between_tokens() {
+        assert_eq!(
+            Value::from_json(" { \"a\" : [ 1 , null ] } \n").unwrap(),
+            read(r#"{"a" [1 nil]}"#)
+        );
+    }
+
+    #[test]
+    fn non_string_keys_are_rejected() {
+        assert_eq!(
+            read("{1 2}").to_json(),
+            Err(JsonError::UnsupportedKey("1".to_owned()))
+        );
+        assert_eq!(
+            read("[{nil 2}]").to_json(),
+            Err(JsonError::UnsupportedKey("nil".to_owned()))
+        );
+    }
+
+    #[test]
+    fn unsupported_values_are_rejected() {
+        assert_eq!(
+            read("(a)").to_json(),
+            Err(JsonError::Unsupported("symbol".to_owned()))
+        );
+        assert_eq!(
+            Value::from(f64::NAN).to_json(),
+            Err(JsonError::Unsupported("float".to_owned()))
+        );
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let nested = |depth| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        let value = Value::from_json(&nested(MAX_DEPTH)).unwrap();
+        assert_eq!(value.to_json().unwrap(), nested(MAX_DEPTH));
+        assert_eq!(
+            Value::from_json(&nested(MAX_DEPTH + 1)),
+            Err(JsonError::TooDeep(MAX_DEPTH))
+        );
+        assert_eq!(
+            Value::from_json(&"[".repeat(1_000_000)),
+            Err(JsonError::TooDeep(MAX_DEPTH))
+        );
+        assert_eq!(
+            Value::from_json(&r#"{"a":"#.repeat(1_000_000)),
+            Err(JsonError::TooDeep(MAX_DEPTH * 5))
+        );
+    }
+
+    #[test]
+    fn malformed_input_is_rejected() {
+        assert_eq!(
+            Value::from_json("[1,"),
+            Err(JsonError::UnexpectedEndOfInput(3))
+        );
+        assert_eq!(
+            Value::from_json("[1 2]"),
+            Err(JsonError::UnexpectedCharacter('2', 3))
+        );
+        assert_eq!(
+            Value::from_json("{1: 2}"),
+            Err(JsonError::UnexpectedCharacter('1', 1))
+        );
+        assert_eq!(
+            Value::from_json("nul"),
+            Err(JsonError::UnexpectedEndOfInput(3))
+        );
+        assert_eq!(
+            Value::from_json("1 2"),
+            Err(JsonError::UnexpectedCharacter('2', 2))
+        );
+        assert_eq!(
+            Value::from_json(r#""\x""#),
+            Err(JsonError::InvalidEscape(1))
+        );
+        assert_eq!(
+            Value::from_json(""),
+            Err(JsonError::UnexpectedEndOfInput(0))
+        );
+    }
+}
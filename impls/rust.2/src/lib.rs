@@ -1,4 +1,123 @@
+use std::fmt::{self, Display, Formatter};
+
+use env::Env;
+use eval::EvalError;
+use reader::ReadError;
+
+pub mod completion;
+pub mod core;
+pub mod env;
+pub mod eval;
+pub mod json;
+pub mod map;
 pub mod parser;
 pub mod printer;
 pub mod reader;
+pub mod repl;
+#[cfg(feature = "serde")]
+mod serialize;
+pub mod symbol;
 pub mod types;
+
+/// Errors that can be raised anywhere in the read-eval-print pipeline.
+#[derive(Debug, PartialEq, Eq)]
+pub enum MalError {
+    /// An error occurred while reading the input.
+    Read(ReadError),
+    /// An error occurred while evaluating the input.
+    Eval(EvalError),
+}
+
+impl Display for MalError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            MalError::Read(error) => write!(f, "{error}"),
+            MalError::Eval(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl From<ReadError> for MalError {
+    fn from(error: ReadError) -> Self {
+        MalError::Read(error)
+    }
+}
+
+impl From<EvalError> for MalError {
+    fn from(error: EvalError) -> Self {
+        MalError::Eval(error)
+    }
+}
+
+/// Read, evaluate, and print a string of mal source code.
+///
+/// The first form in `input` is read, evaluated in `env`, and the result is
+/// formatted readably with [pr_str](crate::printer::pr_str).
+///
+/// # Arguments
+///
+/// * `input` - A string slice containing some mal source code.
+/// * `env` - The environment to evaluate in. Definitions made by `input` are
+///   visible to later calls using the same environment.
+///
+/// # Examples
+///
+/// ```
+/// use mal::{core::default_env, rep};
+///
+/// let env = default_env();
+/// rep("(def! x 40)", &env).unwrap();
+/// assert_eq!(rep("(+ x 2)", &env).unwrap(), "42");
+/// ```
+pub fn rep(input: &str, env: &Env) -> Result<String, MalError> {
+    rep_styled(input, env, false)
+}
+
+/// Read every form in `input`, then evaluate and print each of them in turn.
+///
+/// Returns one result per form evaluated. If `input` cannot be read, nothing
+/// is evaluated and the only result is the read error. Evaluation stops after
+/// the first form that fails, whose error is the last result.
+///
+/// # Examples
+///
+/// ```
+/// use mal::{core::default_env, rep_all};
+///
+/// let env = default_env();
+/// let results = rep_all("(def! a 1) (+ a 1)", &env);
+/// assert_eq!(results, [Ok("1".to_owned()), Ok("2".to_owned())]);
+/// ```
+pub fn rep_all(input: &str, env: &Env) -> Vec<Result<String, MalError>> {
+    rep_all_styled(input, env, false)
+}
+
+/// Like [rep_all], but each result is formatted with
+/// [pr_str_styled](crate::printer::pr_str_styled) and colored if `color` is
+/// set.
+pub fn rep_all_styled(input: &str, env: &Env, color: bool) -> Vec<Result<String, MalError>> {
+    let forms = match reader::read_str_all(input) {
+        Ok(forms) => forms,
+        Err(error) => return vec![Err(error.into())],
+    };
+    let mut results = vec![];
+    for form in forms {
+        match eval::eval(form, env) {
+            Ok(value) => results.push(Ok(printer::pr_str_styled(&value, false, color))),
+            Err(error) => {
+                results.push(Err(error.into()));
+                break;
+            }
+        }
+    }
+    results
+}
+
+/// Like [rep], but the result is formatted with
+/// [pr_str_styled](crate::printer::pr_str_styled) and colored if `color` is
+/// set.
+pub fn rep_styled(input: &str, env: &Env, color: bool) -> Result<String, MalError> {
+    let ast = reader::read_str(input)?;
+    let value = eval::eval(ast, env)?;
+    Ok(printer::pr_str_styled(&value, false, color))
+}
@@ -0,0 +1,205 @@
+//! The insertion-ordered map backing mal hash-maps.
+use std::{collections::HashMap, rc::Rc};
+
+use crate::types::{Atom, Value};
+
+/// A map of [atoms](crate::types::Atom) to values that remembers the order in
+/// which keys were first inserted.
+///
+/// Iteration and printing follow insertion order, but two maps are equal if
+/// they hold the same entries in any order. Metadata is ignored when comparing
+/// maps.
+///
+/// Any atom can be a key, including `nil`, `true`, `false`, numbers, and
+/// symbols, while collections and functions cannot. The reader and builtins
+/// such as `assoc` and `hash-map` enforce the same rule, so a map literal and
+/// the equivalent `assoc` call always agree.
+///
+/// # Examples
+///
+/// ```
+/// use mal::{map::Map, types::{Atom, Value}};
+///
+/// let mut map = Map::new();
+/// map.insert(Atom::Keyword("z".to_owned()), Value::from(1));
+/// map.insert(Atom::Keyword("a".to_owned()), Value::from(2));
+/// let keys: Vec<_> = map.keys().cloned().collect();
+/// assert_eq!(keys, [Atom::Keyword("z".to_owned()), Atom::Keyword("a".to_owned())]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Map {
+    entries: Vec<(Atom, Value)>,
+    index: HashMap<Atom, usize>,
+    pub(crate) meta: Option<Rc<Value>>,
+}
+
+impl Map {
+    /// Create an empty map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the map has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The value for `key`, if there is one.
+    pub fn get(&self, key: &Atom) -> Option<&Value> {
+        self.index.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    /// Whether the map has an entry for `key`.
+    pub fn contains_key(&self, key: &Atom) -> bool {
+        self.index.contains_key(key)
+    }
+
+    /// Set the value for `key`, returning the previous value if there was one.
+    ///
+    /// A key that is already present keeps its original position.
+    pub fn insert(&mut self, key: Atom, value: Value) -> Option<Value> {
+        if let Some(&i) = self.index.get(&key) {
+            return Some(std::mem::replace(&mut self.entries[i].1, value));
+        }
+        self.index.insert(key.clone(), self.entries.len());
+        self.entries.push((key, value));
+        None
+    }
+
+    /// Remove the entry for `key`, returning its value if there was one.
+    ///
+    /// The remaining entries keep their relative order.
+    pub fn remove(&mut self, key: &Atom) -> Option<Value> {
+        let i = self.index.remove(key)?;
+        let (_, value) = self.entries.remove(i);
+        for (key, _) in &self.entries[i..] {
+            *self.index.get_mut(key).expect("key is indexed") -= 1;
+        }
+        Some(value)
+    }
+
+    /// This map with its metadata replaced by `meta`.
+    pub(crate) fn with_meta(self, meta: Option<Rc<Value>>) -> Self {
+        Self { meta, ..self }
+    }
+
+    /// Iterate over the entries in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Atom, &Value)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    /// Iterate over the keys in insertion order.
+    pub fn keys(&self) -> impl Iterator<Item = &Atom> {
+        self.entries.iter().map(|(key, _)| key)
+    }
+
+    /// Iterate over the values in insertion order.
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|(_, value)| value)
+    }
+}
+
+impl PartialEq for Map {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl Eq for Map {}
+
+impl FromIterator<(Atom, Value)> for Map {
+    fn from_iter<I: IntoIterator<Item = (Atom, Value)>>(iter: I) -> Self {
+        let mut map = Map::new();
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+impl<const N: usize> From<[(Atom, Value); N]> for Map {
+    fn from(entries: [(Atom, Value); N]) -> Self {
+        entries.into_iter().collect()
+    }
+}
+
+impl IntoIterator for Map {
+    type Item = (Atom, Value);
+    type IntoIter = std::vec::IntoIter<(Atom, Value)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Map {
+    type Item = (&'a Atom, &'a Value);
+    type IntoIter = std::iter::Map<
+        std::slice::Iter<'a, (Atom, Value)>,
+        fn(&'a (Atom, Value)) -> (&'a Atom, &'a Value),
+    >;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Map;
+    use crate::types::{Atom, Value};
+
+    fn key(name: &str) -> Atom {
+        Atom::Keyword(name.to_owned())
+    }
+
+    #[test]
+    fn test_insertion_order() {
+        let mut map = Map::from([(key("z"), Value::from(1)), (key("a"), Value::from(2))]);
+        map.insert(key("m"), Value::from(3));
+        map.insert(key("z"), Value::from(4));
+        let entries: Vec<_> = map.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        assert_eq!(
+            entries,
+            [
+                (key("z"), Value::from(4)),
+                (key("a"), Value::from(2)),
+                (key("m"), Value::from(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_remove_keeps_order() {
+        let mut map = Map::from([
+            (key("a"), Value::from(1)),
+            (key("b"), Value::from(2)),
+            (key("c"), Value::from(3)),
+        ]);
+        assert_eq!(map.remove(&key("a")), Some(Value::from(1)));
+        assert_eq!(map.remove(&key("a")), None);
+        assert_eq!(
+            map.keys().cloned().collect::<Vec<_>>(),
+            [key("b"), key("c")]
+        );
+        assert_eq!(map.get(&key("c")), Some(&Value::from(3)));
+    }
+
+    #[test]
+    fn test_equality_ignores_order() {
+        let a = Map::from([(key("a"), Value::from(1)), (key("b"), Value::from(2))]);
+        let b = Map::from([(key("b"), Value::from(2)), (key("a"), Value::from(1))]);
+        assert_eq!(a, b);
+        let c = Map::from([(key("a"), Value::from(1)), (key("b"), Value::from(3))]);
+        assert_ne!(a, c);
+        assert_ne!(a, Map::from([(key("a"), Value::from(1))]));
+    }
+}
@@ -1,10 +1,14 @@
 //! Parsing of strings into token streams.
 use std::{
-    collections::HashMap,
     fmt::{self, Display, Formatter},
-    num::ParseIntError,
+    iter::Peekable,
+    num::{ParseFloatError, ParseIntError},
+    ops::Range,
+    str::CharIndices,
 };
 
+use crate::types::Float;
+
 /// Token types used in the process of tokenizing mal source code.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Token {
@@ -20,18 +24,61 @@ pub enum Token {
     SpliceUnquote,
     Deref,
     WithMeta,
+    Discard,
     Symbol(String),
     Keyword(String),
     String(String),
-    Int(i32),
+    Char(char),
+    Int(i64),
+    Float(Float),
     Nil,
     True,
     False,
 }
 
-pub(crate) struct Parser {
-    input: String,
+/// Characters that are written by name rather than literally, such as
+/// `\newline`.
+pub(crate) const CHAR_NAMES: &[(&str, char)] = &[
+    ("newline", '\n'),
+    ("space", ' '),
+    ("tab", '\t'),
+    ("return", '\r'),
+    ("backspace", '\u{8}'),
+    ("formfeed", '\u{c}'),
+];
+
+/// Look up the character written as `\name`, where `name` is either one of
+/// [CHAR_NAMES] or `u` followed by four hex digits.
+fn char_from_name(name: &str) -> Option<char> {
+    if let Some((_, c)) = CHAR_NAMES.iter().find(|(n, _)| *n == name) {
+        return Some(*c);
+    }
+    let hex = name
+        .strip_prefix('u')
+        .filter(|hex| hex.len() == 4 && hex.chars().all(|c| c.is_ascii_hexdigit()))?;
+    u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+}
+
+/// A tokenizer that lazily produces [tokens](crate::parser::Token) from a
+/// string of mal source code.
+///
+/// Iteration stops after the first error.
+///
+/// # Examples
+///
+/// ```
+/// use mal::parser::{Parser, Token};
+///
+/// let mut parser = Parser::new("(+ 1)");
+/// assert_eq!(parser.next(), Some(Ok(Token::LParen)));
+/// assert_eq!(parser.next(), Some(Ok(Token::Symbol("+".to_owned()))));
+/// ```
+pub struct Parser<'a> {
+    input: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    /// Byte offset of the next character in `input`.
     pos: usize,
+    failed: bool,
 }
 
 /// Errors that can be raised while parsing.
@@ -46,11 +93,58 @@ pub enum ParseError {
     },
     /// The end of the input string was reached before parsing was completed.
     UnexpectedEndOfInput(usize),
+    /// The end of the input string was reached inside a string. The position
+    /// is that of the opening quote.
+    UnterminatedString(usize),
     /// The parser encountered a backslash followed by a character that does not
     /// form a known escape sequence.
     UnknownEscapeSequence(char, usize),
+    /// A `\x` escape sequence in a string was not followed by two hex digits.
+    InvalidHexEscape(usize),
+    /// The parser encountered a character literal such as `\foo` that is
+    /// neither a single character nor a known character name.
+    UnknownCharacterName(String, usize),
+    /// A `:` was not followed by a keyword name. Like `(keyword "")`, this is
+    /// rejected because a nameless keyword would not read back.
+    EmptyKeyword(usize),
     /// Parsing an integer value failed for some reason.
     ParseInt(ParseIntError, usize),
+    /// Parsing a floating point value failed for some reason.
+    ParseFloat(ParseFloatError, usize),
+}
+
+impl ParseError {
+    /// The byte offset in the input at which the error occurred.
+    pub fn pos(&self) -> usize {
+        match self {
+            ParseError::UnexpectedCharacter { pos, .. }
+            | ParseError::UnexpectedEndOfInput(pos)
+            | ParseError::UnterminatedString(pos)
+            | ParseError::UnknownEscapeSequence(_, pos)
+            | ParseError::InvalidHexEscape(pos)
+            | ParseError::UnknownCharacterName(_, pos)
+            | ParseError::EmptyKeyword(pos)
+            | ParseError::ParseInt(_, pos)
+            | ParseError::ParseFloat(_, pos) => *pos,
+        }
+    }
+
+    /// A short identifier for the kind of error, such as `"unexpected-eof"`.
+    ///
+    /// Unlike the [Display] output, codes are stable and can be matched on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::UnexpectedCharacter { .. } => "unexpected-character",
+            ParseError::UnexpectedEndOfInput(_) => "unexpected-eof",
+            ParseError::UnterminatedString(_) => "unterminated-string",
+            ParseError::UnknownEscapeSequence(..) => "unknown-escape",
+            ParseError::InvalidHexEscape(_) => "invalid-hex-escape",
+            ParseError::UnknownCharacterName(..) => "unknown-character-name",
+            ParseError::EmptyKeyword(_) => "empty-keyword",
+            ParseError::ParseInt(..) => "invalid-int",
+            ParseError::ParseFloat(..) => "invalid-float",
+        }
+    }
 }
 
 impl Display for ParseError {
@@ -66,19 +160,40 @@ impl Display for ParseError {
             ParseError::UnexpectedEndOfInput(pos) => {
                 write!(f, "unexpected end of input at position {pos}")
             }
+            ParseError::UnterminatedString(pos) => {
+                write!(
+                    f,
+                    "unexpected end of input in string starting at position {pos}"
+                )
+            }
             ParseError::ParseInt(e, pos) => write!(f, "parse int error at position {pos}: {e}"),
+            ParseError::ParseFloat(e, pos) => {
+                write!(f, "parse float error at position {pos}: {e}")
+            }
             ParseError::UnknownEscapeSequence(c, pos) => {
                 write!(f, "unknown escape sequence: \\{c} at position {pos}")
             }
+            ParseError::InvalidHexEscape(pos) => {
+                write!(f, "expected two hex digits after \\x at position {pos}")
+            }
+            ParseError::UnknownCharacterName(name, pos) => {
+                write!(f, "unknown character name: \\{name} at position {pos}")
+            }
+            ParseError::EmptyKeyword(pos) => {
+                write!(f, "keyword with no name at position {pos}")
+            }
         }
     }
 }
 
-impl Parser {
-    pub(crate) fn new(input: &str) -> Self {
+impl<'a> Parser<'a> {
+    /// Create a parser over the given mal source code.
+    pub fn new(input: &'a str) -> Self {
         Self {
-            input: input.to_owned(),
+            input,
+            chars: input.char_indices().peekable(),
             pos: 0,
+            failed: false,
         }
     }
 
@@ -87,11 +202,13 @@ impl Parser {
     }
 
     fn peek(&mut self) -> Option<char> {
-        self.input[self.pos..].chars().next()
+        self.chars.peek().map(|(_, c)| *c)
     }
 
     fn consume_char(&mut self) {
-        self.pos += 1;
+        if let Some((i, c)) = self.chars.next() {
+            self.pos = i + c.len_utf8();
+        }
     }
 
     fn expect_char(&mut self, expected: char) -> Result<(), ParseError> {
@@ -120,42 +237,68 @@ impl Parser {
     }
 
     fn take_while(&mut self, predicate: fn(char) -> bool) -> String {
-        let mut result = String::new();
+        let start = self.pos;
         while let Some(c) = self.peek() {
             if !predicate(c) {
                 break;
             }
-            result.push(c);
             self.consume_char();
         }
-        result
+        self.input[start..self.pos].to_owned()
     }
 
-    fn parse_bare_sequence(&mut self) -> Token {
-        let sequence = self.take_while(Self::is_symbol_character);
-
-        let named_types = HashMap::from([
-            ("nil".to_owned(), Token::Nil),
-            ("true".to_owned(), Token::True),
-            ("false".to_owned(), Token::False),
-        ]);
+    /// Returns true if `sequence` should be read as a number: an optional `-`
+    /// or `+` sign followed by a digit. A lone sign, or a sign followed by
+    /// anything else, is a symbol.
+    fn is_number(sequence: &str) -> bool {
+        let unsigned = sequence.strip_prefix(['-', '+']).unwrap_or(sequence);
+        unsigned.starts_with(|c: char| c.is_ascii_digit())
+    }
 
-        if let Some(token) = named_types.get(&sequence) {
-            token.clone()
+    /// Parse `sequence`, which starts at `start`, as a float if it contains a
+    /// decimal point or an exponent, or as an integer otherwise.
+    fn parse_number(sequence: &str, start: usize) -> Result<Token, ParseError> {
+        if sequence.contains(['.', 'e', 'E']) {
+            sequence
+                .parse::<f64>()
+                .map(|float| Token::Float(Float(float)))
+                .map_err(|e| ParseError::ParseFloat(e, start))
         } else {
-            match sequence.parse::<i32>() {
-                Ok(int) => Token::Int(int),
-                Err(_) => Token::Symbol(sequence),
-            }
+            sequence
+                .parse::<i64>()
+                .map(Token::Int)
+                .map_err(|e| ParseError::ParseInt(e, start))
+        }
+    }
+
+    fn parse_bare_sequence(&mut self) -> Result<Token, ParseError> {
+        let start = self.pos;
+        let sequence = self.take_while(Self::is_symbol_character);
+
+        match sequence.as_str() {
+            "nil" => Ok(Token::Nil),
+            "true" => Ok(Token::True),
+            "false" => Ok(Token::False),
+            "##Inf" => Ok(Token::Float(Float(f64::INFINITY))),
+            "##-Inf" => Ok(Token::Float(Float(f64::NEG_INFINITY))),
+            "##NaN" => Ok(Token::Float(Float(f64::NAN))),
+            _ if Self::is_number(&sequence) => Self::parse_number(&sequence, start),
+            _ => Ok(Token::Symbol(sequence)),
         }
     }
 
     fn parse_keyword(&mut self) -> Result<Token, ParseError> {
+        let start = self.pos;
         self.expect_char(':')?;
-        Ok(Token::Keyword(self.take_while(char::is_alphanumeric)))
+        let name = self.take_while(Self::is_symbol_character);
+        if name.is_empty() {
+            return Err(ParseError::EmptyKeyword(start));
+        }
+        Ok(Token::Keyword(name))
     }
 
     fn parse_string(&mut self) -> Result<Token, ParseError> {
+        let start = self.pos;
         self.expect_char('"')?;
 
         let mut result = String::new();
@@ -172,6 +315,15 @@ impl Parser {
                     escaping = false;
                     self.consume_char();
                 }
+                '0' if escaping => {
+                    result.push('\0');
+                    escaping = false;
+                    self.consume_char();
+                }
+                'x' if escaping => {
+                    result.push(self.parse_hex_escape()?);
+                    escaping = false;
+                }
                 '\\' if !escaping => {
                     escaping = true;
                     self.consume_char();
@@ -185,14 +337,88 @@ impl Parser {
             }
         }
 
+        if self.peek().is_none() {
+            return Err(ParseError::UnterminatedString(start));
+        }
         self.expect_char('"')?;
         Ok(Token::String(result))
     }
 
+    /// Parse the `xNN` part of a `\xNN` string escape, producing the character
+    /// with code point `NN`.
+    fn parse_hex_escape(&mut self) -> Result<char, ParseError> {
+        let start = self.pos;
+        self.expect_char('x')?;
+        let code = self
+            .input
+            .get(self.pos..self.pos + 2)
+            .filter(|hex| hex.chars().all(|c| c.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .ok_or(ParseError::InvalidHexEscape(start))?;
+        self.consume_char();
+        self.consume_char();
+        Ok(char::from(code))
+    }
+
+    /// Parse a raw string such as `#"C:\path"#`, in which backslashes and
+    /// quotes are literal and only `"#` ends the string.
+    fn parse_raw_string(&mut self) -> Result<Token, ParseError> {
+        let start = self.pos;
+        self.expect_char('#')?;
+        self.expect_char('"')?;
+
+        let Some(len) = self.input[self.pos..].find("\"#") else {
+            return Err(ParseError::UnterminatedString(start));
+        };
+        let result = self.input[self.pos..self.pos + len].to_owned();
+        let end = self.pos + len + 2;
+        while self.pos < end {
+            self.consume_char();
+        }
+        Ok(Token::String(result))
+    }
+
+    fn parse_char(&mut self) -> Result<Token, ParseError> {
+        let start = self.pos;
+        self.expect_char('\\')?;
+
+        let first = match self.peek() {
+            Some(c) if c == ',' || c.is_whitespace() => {
+                return Err(ParseError::UnexpectedCharacter {
+                    got: '\\',
+                    expected: None,
+                    pos: start,
+                })
+            }
+            Some(c) => c,
+            None => return Err(ParseError::UnexpectedEndOfInput(self.pos)),
+        };
+        self.consume_char();
+        if !Self::is_symbol_character(first) {
+            return Ok(Token::Char(first));
+        }
+
+        let rest = self.take_while(Self::is_symbol_character);
+        if rest.is_empty() {
+            return Ok(Token::Char(first));
+        }
+        let name = format!("{first}{rest}");
+        char_from_name(&name)
+            .map(Token::Char)
+            .ok_or(ParseError::UnknownCharacterName(name, start))
+    }
+
+    fn consume_comment(&mut self) {
+        while let Some(c) = self.peek() {
+            self.consume_char();
+            if c == '\n' {
+                break;
+            }
+        }
+    }
+
     fn parse_token(&mut self) -> Result<Option<Token>, ParseError> {
-        self.consume_whitespace();
         match self.peek() {
-            Some(';') => Ok(None),
             Some('(') => {
                 self.consume_char();
                 Ok(Some(Token::LParen))
@@ -245,7 +471,16 @@ impl Parser {
             }
             Some(':') => self.parse_keyword().map(Some),
             Some('"') => self.parse_string().map(Some),
-            Some(c) if Self::is_symbol_character(c) => Ok(Some(self.parse_bare_sequence())),
+            Some('#') if self.input[self.pos..].starts_with("#\"") => {
+                self.parse_raw_string().map(Some)
+            }
+            Some('#') if self.input[self.pos..].starts_with("#_") => {
+                self.consume_char();
+                self.consume_char();
+                Ok(Some(Token::Discard))
+            }
+            Some('\\') => self.parse_char().map(Some),
+            Some(c) if Self::is_symbol_character(c) => self.parse_bare_sequence().map(Some),
             Some(c) => Err(ParseError::UnexpectedCharacter {
                 got: c,
                 expected: None,
@@ -255,22 +490,163 @@ impl Parser {
         }
     }
 
-    fn tokenize(&mut self) -> Result<Vec<Token>, ParseError> {
-        let mut tokens = vec![];
-        while let Some(token) = self.parse_token()? {
-            tokens.push(token);
+    /// Parse the next token along with the byte range it spans in the input.
+    fn parse_spanned_token(&mut self) -> Option<Result<(Token, Range<usize>), ParseError>> {
+        if self.failed {
+            return None;
         }
-        Ok(tokens)
+
+        self.consume_whitespace();
+        while self.peek() == Some(';') {
+            self.consume_comment();
+            self.consume_whitespace();
+        }
+
+        let start = self.pos;
+        let result = self.parse_token();
+        self.failed = result.is_err();
+        result
+            .map(|token| token.map(|token| (token, start..self.pos)))
+            .transpose()
     }
 }
 
-pub(crate) fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
-    Parser::new(input).tokenize()
+impl Iterator for Parser<'_> {
+    type Item = Result<Token, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.parse_spanned_token()
+            .map(|result| result.map(|(token, _)| token))
+    }
+}
+
+/// The line and column, both counting from one, of the byte offset `pos` in
+/// `input`.
+///
+/// Every newline starts a new line, including the one ending a `;` comment.
+/// Columns count characters rather than bytes. An offset past the end of
+/// `input` is treated as the end.
+///
+/// # Examples
+///
+/// ```
+/// use mal::parser::line_col;
+///
+/// let input = "; comment\n(+ 1 ]";
+/// assert_eq!(line_col(input, 15), (2, 6));
+/// ```
+pub fn line_col(input: &str, pos: usize) -> (usize, usize) {
+    let before = input
+        .char_indices()
+        .take_while(|&(i, _)| i < pos)
+        .map(|(_, c)| c);
+    let mut line = 1;
+    let mut column = 1;
+    for c in before {
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    (line, column)
+}
+
+/// Split a string of mal source code into a vector of tokens.
+///
+/// This is a convenience wrapper that collects a [Parser].
+pub fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    Parser::new(input).collect()
+}
+
+/// Split a string of mal source code into tokens paired with the byte range
+/// each one spans in `input`.
+///
+/// Whitespace and comments are not part of any range, so slicing `input` with
+/// a range gives exactly the source text of its token.
+///
+/// # Examples
+///
+/// ```
+/// use mal::parser::{tokenize_spanned, Token};
+///
+/// let input = "(+ 1)";
+/// let tokens = tokenize_spanned(input).unwrap();
+/// assert_eq!(tokens[1], (Token::Symbol("+".to_owned()), 1..2));
+/// assert_eq!(&input[tokens[1].1.clone()], "+");
+/// ```
+pub fn tokenize_spanned(input: &str) -> Result<Vec<(Token, Range<usize>)>, ParseError> {
+    let mut parser = Parser::new(input);
+    std::iter::from_fn(|| parser.parse_spanned_token()).collect()
+}
+
+/// Like [tokenize_spanned], but on an error the tokens before it are returned
+/// along with the error, so that the start of the input can still be read.
+pub(crate) fn tokenize_spanned_partial(
+    input: &str,
+) -> (Vec<(Token, Range<usize>)>, Option<ParseError>) {
+    let mut parser = Parser::new(input);
+    let mut tokens = vec![];
+    while let Some(result) = parser.parse_spanned_token() {
+        match result {
+            Ok(token) => tokens.push(token),
+            Err(error) => return (tokens, Some(error)),
+        }
+    }
+    (tokens, None)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{tokenize, Token};
+    use super::{line_col, tokenize, tokenize_spanned, ParseError, Parser, Token};
+    use crate::types::Float;
+
+    #[test]
+    fn test_line_col() {
+        let input = "ab\nλx\n";
+        assert_eq!(line_col(input, 0), (1, 1));
+        assert_eq!(line_col(input, 2), (1, 3));
+        assert_eq!(line_col(input, 3), (2, 1));
+        assert_eq!(line_col(input, 5), (2, 2));
+        assert_eq!(line_col(input, 7), (3, 1));
+        assert_eq!(line_col(input, 100), (3, 1));
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let cases = [
+            (
+                ParseError::UnexpectedCharacter {
+                    got: 'x',
+                    expected: None,
+                    pos: 0,
+                },
+                "unexpected-character",
+            ),
+            (ParseError::UnexpectedEndOfInput(0), "unexpected-eof"),
+            (ParseError::UnterminatedString(0), "unterminated-string"),
+            (ParseError::UnknownEscapeSequence('q', 0), "unknown-escape"),
+            (ParseError::InvalidHexEscape(0), "invalid-hex-escape"),
+            (
+                ParseError::UnknownCharacterName("foo".to_owned(), 0),
+                "unknown-character-name",
+            ),
+            (ParseError::EmptyKeyword(0), "empty-keyword"),
+            (
+                ParseError::ParseInt("x".parse::<i64>().unwrap_err(), 0),
+                "invalid-int",
+            ),
+            (
+                ParseError::ParseFloat("x".parse::<f64>().unwrap_err(), 0),
+                "invalid-float",
+            ),
+        ];
+        for (error, code) in cases {
+            assert_eq!(error.code(), code, "{error}");
+        }
+        assert_eq!(tokenize("\"abc").unwrap_err().code(), "unterminated-string");
+    }
 
     #[test]
     fn test_parser() {
@@ -294,6 +670,36 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_unterminated_string() {
+        assert_eq!(
+            tokenize("(a \"unterminated"),
+            Err(ParseError::UnterminatedString(3))
+        );
+        assert_eq!(
+            tokenize("\"ends in escape\\"),
+            Err(ParseError::UnterminatedString(0))
+        );
+        let input = "(def! s\n  \"line one\nline two)";
+        let error = tokenize(input).unwrap_err();
+        assert_eq!(error, ParseError::UnterminatedString(10));
+        assert_eq!(
+            error.to_string(),
+            "unexpected end of input in string starting at position 10"
+        );
+    }
+
+    #[test]
+    fn test_hex_and_nul_escapes() {
+        assert_eq!(
+            tokenize(r#""\x41\x7e\0""#).unwrap(),
+            vec![Token::String("A~\0".to_owned())]
+        );
+        assert_eq!(tokenize(r#""\x4""#), Err(ParseError::InvalidHexEscape(2)));
+        assert_eq!(tokenize(r#""\x""#), Err(ParseError::InvalidHexEscape(2)));
+        assert_eq!(tokenize(r#""\xzz""#), Err(ParseError::InvalidHexEscape(2)));
+    }
+
     #[test]
     fn test_escape_sequences() {
         let input = r#""hello \" escaped \\ world\n""#;
@@ -310,4 +716,279 @@ mod tests {
         let tokens = tokenize(input).unwrap();
         assert_eq!(tokens, vec![]);
     }
+
+    #[test]
+    fn test_comment_between_tokens() {
+        let input = "; header\n(1 ; one\n 2) ; trailing";
+        let tokens = tokenize(input).unwrap();
+        assert_eq!(
+            tokens,
+            vec![Token::LParen, Token::Int(1), Token::Int(2), Token::RParen]
+        );
+    }
+
+    #[test]
+    fn test_keywords_take_symbol_characters() {
+        let tokens = tokenize("(:ns/key :kebab-case :done? :a)").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LParen,
+                Token::Keyword("ns/key".to_owned()),
+                Token::Keyword("kebab-case".to_owned()),
+                Token::Keyword("done?".to_owned()),
+                Token::Keyword("a".to_owned()),
+                Token::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_keyword() {
+        assert_eq!(tokenize(":"), Err(ParseError::EmptyKeyword(0)));
+        assert_eq!(tokenize("[:a : :b]"), Err(ParseError::EmptyKeyword(4)));
+        assert_eq!(tokenize("(:)"), Err(ParseError::EmptyKeyword(1)));
+    }
+
+    #[test]
+    fn test_named_types() {
+        let input = "nil true false nilly truthy falsey";
+        let tokens = tokenize(input).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Nil,
+                Token::True,
+                Token::False,
+                Token::Symbol("nilly".to_owned()),
+                Token::Symbol("truthy".to_owned()),
+                Token::Symbol("falsey".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_large_input() {
+        let input = "(+ 1 \"two\" :three) ".repeat(10_000);
+        let tokens = tokenize(&input).unwrap();
+        assert_eq!(tokens.len(), 60_000);
+        assert_eq!(tokens[59_999], Token::RParen);
+    }
+
+    #[test]
+    fn test_long_symbol() {
+        let symbol = "a".repeat(1_000_000);
+        let tokens = tokenize(&format!("({symbol})")).unwrap();
+        assert_eq!(
+            tokens,
+            vec![Token::LParen, Token::Symbol(symbol), Token::RParen]
+        );
+    }
+
+    #[test]
+    fn test_multibyte_characters() {
+        let input = "(λ :ünï \"héllo → wörld\")";
+        let tokens = tokenize(input).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LParen,
+                Token::Symbol("λ".to_owned()),
+                Token::Keyword("ünï".to_owned()),
+                Token::String("héllo → wörld".to_owned()),
+                Token::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_iterator_matches_tokenize() {
+        let input = "(def! a [1 \"two\" :three]) ; comment\n@a";
+        let tokens = Parser::new(input).collect::<Result<Vec<_>, _>>();
+        assert_eq!(tokens, tokenize(input));
+        assert_eq!(tokens.unwrap().len(), 11);
+    }
+
+    #[test]
+    fn test_iterator_stops_after_error() {
+        let mut parser = Parser::new("1 \\ 2");
+        assert_eq!(parser.next(), Some(Ok(Token::Int(1))));
+        assert_eq!(
+            parser.next(),
+            Some(Err(ParseError::UnexpectedCharacter {
+                got: '\\',
+                expected: None,
+                pos: 2
+            }))
+        );
+        assert_eq!(parser.next(), None);
+    }
+
+    #[test]
+    fn test_signed_numbers() {
+        let symbol = |s: &str| Token::Symbol(s.to_owned());
+        assert_eq!(tokenize("-5"), Ok(vec![Token::Int(-5)]));
+        assert_eq!(tokenize("+5"), Ok(vec![Token::Int(5)]));
+        assert_eq!(tokenize("-"), Ok(vec![symbol("-")]));
+        assert_eq!(tokenize("+"), Ok(vec![symbol("+")]));
+        assert_eq!(tokenize("++"), Ok(vec![symbol("++")]));
+        assert_eq!(tokenize("++5"), Ok(vec![symbol("++5")]));
+        assert_eq!(tokenize("+5.5"), Ok(vec![Token::Float(Float(5.5))]));
+        assert_eq!(tokenize("-x"), Ok(vec![symbol("-x")]));
+        assert_eq!(tokenize("->>"), Ok(vec![symbol("->>")]));
+        assert_eq!(tokenize("- 5"), Ok(vec![symbol("-"), Token::Int(5)]));
+    }
+
+    #[test]
+    fn test_malformed_numbers() {
+        assert!(matches!(
+            tokenize("(1 -12abc)"),
+            Err(ParseError::ParseInt(_, 3))
+        ));
+        assert!(matches!(
+            tokenize("99999999999999999999"),
+            Err(ParseError::ParseInt(_, 0))
+        ));
+    }
+
+    #[test]
+    fn test_chars() {
+        let input = r"(\a \( \newline \space \é \λ)";
+        let tokens = tokenize(input).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LParen,
+                Token::Char('a'),
+                Token::Char('('),
+                Token::Char('\n'),
+                Token::Char(' '),
+                Token::Char('é'),
+                Token::Char('λ'),
+                Token::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bad_chars() {
+        assert_eq!(
+            tokenize(r"(\nope)"),
+            Err(ParseError::UnknownCharacterName("nope".to_owned(), 1))
+        );
+        assert_eq!(
+            tokenize(r"\u12"),
+            Err(ParseError::UnknownCharacterName("u12".to_owned(), 0))
+        );
+        assert_eq!(
+            tokenize(r"\u+041"),
+            Err(ParseError::UnknownCharacterName("u+041".to_owned(), 0))
+        );
+        assert_eq!(tokenize("\\"), Err(ParseError::UnexpectedEndOfInput(1)));
+    }
+
+    #[test]
+    fn test_raw_strings() {
+        let input = r##"(#"C:\path\to "file" \d+"# #""# #"a"b"#)"##;
+        let tokens = tokenize(input).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::LParen,
+                Token::String(r#"C:\path\to "file" \d+"#.to_owned()),
+                Token::String("".to_owned()),
+                Token::String("a\"b".to_owned()),
+                Token::RParen,
+            ]
+        );
+        assert_eq!(
+            tokenize("#a #b"),
+            Ok(vec![
+                Token::Symbol("#a".to_owned()),
+                Token::Symbol("#b".to_owned())
+            ])
+        );
+    }
+
+    #[test]
+    fn test_unterminated_raw_string() {
+        let input = r#"(#"abc" def)"#;
+        assert_eq!(tokenize(input), Err(ParseError::UnterminatedString(1)));
+    }
+
+    #[test]
+    fn test_discard() {
+        assert_eq!(
+            tokenize("(1 #_2 #_#_3)"),
+            Ok(vec![
+                Token::LParen,
+                Token::Int(1),
+                Token::Discard,
+                Token::Int(2),
+                Token::Discard,
+                Token::Discard,
+                Token::Int(3),
+                Token::RParen,
+            ])
+        );
+    }
+
+    #[test]
+    fn test_floats() {
+        assert_eq!(
+            tokenize("1.5 -0.25 +2. 1e3 -1.5E-2 3"),
+            Ok(vec![
+                Token::Float(Float(1.5)),
+                Token::Float(Float(-0.25)),
+                Token::Float(Float(2.0)),
+                Token::Float(Float(1000.0)),
+                Token::Float(Float(-0.015)),
+                Token::Int(3),
+            ])
+        );
+        assert!(matches!(
+            tokenize("1.2.3"),
+            Err(ParseError::ParseFloat(_, 0))
+        ));
+        assert_eq!(tokenize("-.5"), Ok(vec![Token::Symbol("-.5".to_owned())]));
+    }
+
+    #[test]
+    fn test_non_finite_floats() {
+        assert_eq!(
+            tokenize("##Inf ##-Inf"),
+            Ok(vec![
+                Token::Float(Float(f64::INFINITY)),
+                Token::Float(Float(f64::NEG_INFINITY)),
+            ])
+        );
+        assert!(matches!(
+            tokenize("##NaN").unwrap()[..],
+            [Token::Float(Float(nan))] if nan.is_nan()
+        ));
+        assert_eq!(
+            tokenize("##inf"),
+            Ok(vec![Token::Symbol("##inf".to_owned())])
+        );
+    }
+
+    #[test]
+    fn test_tokenize_spanned() {
+        let input = "(+ 1)";
+        let tokens = tokenize_spanned(input).unwrap();
+        let texts: Vec<_> = tokens
+            .iter()
+            .map(|(_, span)| &input[span.clone()])
+            .collect();
+        assert_eq!(texts, ["(", "+", "1", ")"]);
+        assert_eq!(tokens[2], (Token::Int(1), 3..4));
+
+        let input = "; note\n[\"a b\" , :kw] ~@x";
+        let texts: Vec<_> = tokenize_spanned(input)
+            .unwrap()
+            .into_iter()
+            .map(|(_, span)| &input[span])
+            .collect();
+        assert_eq!(texts, ["[", "\"a b\"", ":kw", "]", "~@", "x"]);
+    }
 }
@@ -1,24 +1,148 @@
 //! Turning mal values into displayable strings.
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    env,
+    ffi::OsStr,
+    io::{self, IsTerminal},
+    str::FromStr,
+};
 
-use crate::types::{Atom, Value};
+use crate::{
+    parser::CHAR_NAMES,
+    types::{Atom, Value},
+};
 
+/// Escape `string` so that reading the result back produces the same string.
+/// Control characters other than newline are written as `\0` or `\xNN`.
 fn escape_string(string: &str) -> String {
-    let table = HashMap::from([('"', "\\\""), ('\\', "\\\\"), ('\n', "\\n")]);
+    let table = HashMap::from([('"', "\\\""), ('\\', "\\\\"), ('\n', "\\n"), ('\0', "\\0")]);
     let mut result = String::with_capacity(string.len());
     for c in string.chars() {
         match table.get(&c) {
             Some(replacement) => result.push_str(replacement),
+            None if c.is_control() => result.push_str(&format!("\\x{:02x}", c as u32)),
             None => result.push(c),
         }
     }
     result
 }
 
-fn pr_list_items(items: Vec<Value>) -> String {
+/// Write `c` as a character literal that reads back as `c`. Characters that
+/// can't follow a backslash directly, such as commas, whitespace, and control
+/// characters, are written by name or as `\uXXXX`.
+fn escape_char(c: char) -> String {
+    match CHAR_NAMES.iter().find(|(_, named)| *named == c) {
+        Some((name, _)) => format!("\\{name}"),
+        None if c.is_control() || c.is_whitespace() || c == ',' => {
+            format!("\\u{:04x}", c as u32)
+        }
+        None => format!("\\{c}"),
+    }
+}
+
+/// Write `float` so that it reads back as the same float. Infinities and NaN
+/// are written as `##Inf`, `##-Inf`, and `##NaN`.
+fn format_float(float: f64) -> String {
+    if float.is_nan() {
+        "##NaN".to_owned()
+    } else if float.is_infinite() {
+        if float > 0.0 { "##Inf" } else { "##-Inf" }.to_owned()
+    } else {
+        format!("{float:?}")
+    }
+}
+
+/// How [pr_str_styled] should color its output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Color only when standard output is a terminal that supports it, and the
+    /// `NO_COLOR` environment variable is unset or empty.
+    Auto,
+    /// Always color.
+    Always,
+    /// Never color.
+    Never,
+}
+
+impl ColorMode {
+    /// Returns true if output should be colored in this mode.
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorMode::Auto => auto_color(
+                io::stdout().is_terminal(),
+                env::var_os("NO_COLOR").as_deref(),
+                env::var_os("TERM").as_deref(),
+            ),
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+}
+
+/// Whether [ColorMode::Auto] colors output, given whether standard output is a
+/// terminal and the values of `NO_COLOR` and `TERM`.
+///
+/// Following <https://no-color.org>, only a non-empty `NO_COLOR` disables color.
+fn auto_color(is_terminal: bool, no_color: Option<&OsStr>, term: Option<&OsStr>) -> bool {
+    is_terminal && no_color.is_none_or(OsStr::is_empty) && term.is_none_or(|term| term != "dumb")
+}
+
+impl FromStr for ColorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorMode::Auto),
+            "always" => Ok(ColorMode::Always),
+            "never" => Ok(ColorMode::Never),
+            other => Err(format!(
+                "invalid color mode: {other}, expected auto, always, or never"
+            )),
+        }
+    }
+}
+
+/// The kinds of text given a distinct color.
+#[derive(Clone, Copy)]
+enum Style {
+    String,
+    Keyword,
+    Number,
+    Constant,
+    Delimiter,
+}
+
+/// Wrap `text` in the ANSI escape codes for `style` if `color` is set.
+fn paint(text: String, style: Style, color: bool) -> String {
+    if !color {
+        return text;
+    }
+    let code = match style {
+        Style::String => 32,
+        Style::Keyword => 35,
+        Style::Number => 33,
+        Style::Constant => 36,
+        Style::Delimiter => 34,
+    };
+    format!("\x1b[{code}m{text}\x1b[0m")
+}
+
+fn delimit(open: &str, inner: String, close: &str, color: bool) -> String {
+    format!(
+        "{}{inner}{}",
+        paint(open.to_owned(), Style::Delimiter, color),
+        paint(close.to_owned(), Style::Delimiter, color)
+    )
+}
+
+fn pr_list_items<'a>(
+    items: impl IntoIterator<Item = &'a Value>,
+    pretty: bool,
+    color: bool,
+) -> String {
     items
         .into_iter()
-        .map(|value| pr_str(value, false))
+        .map(|value| pr_str_styled(value, pretty, color))
         .collect::<Vec<_>>()
         .join(" ")
 }
@@ -27,8 +151,13 @@ fn pr_list_items(items: Vec<Value>) -> String {
 ///
 /// # Arguments
 ///
+/// * `value` - The mal value to format.
 /// * `pretty` - Enables pretty printing of strings. This means the string is
-/// formatted without delimiting quotes and escape sequences are interpreted.
+///   formatted without delimiting quotes and escape sequences are interpreted.
+///   This applies to strings nested inside collections too.
+///   Keywords keep their leading colon either way, as in Clojure where
+///   `(str :a)` is `":a"`, so a keyword is never mistaken for a symbol or a
+///   string once printed.
 ///
 /// # Examples
 ///
@@ -36,82 +165,139 @@ fn pr_list_items(items: Vec<Value>) -> String {
 /// use mal::types::{Value, Atom};
 /// use mal::printer::pr_str;
 ///
-/// let value = Value::Atom(Atom::Symbol("sym".to_owned()));
-/// assert_eq!(pr_str(value, false), "sym");
+/// let value = Value::Atom(Atom::Symbol("sym".into()));
+/// assert_eq!(pr_str(&value, false), "sym");
 ///
 /// let value = Value::Atom(Atom::Keyword("kw".to_owned()));
-/// assert_eq!(pr_str(value, false), ":kw");
+/// assert_eq!(pr_str(&value, false), ":kw");
+/// assert_eq!(pr_str(&value, true), ":kw");
 ///
 /// let value = Value::Atom(Atom::String("hello, world!".to_owned()));
-/// assert_eq!(pr_str(value, false), "\"hello, world!\"");
+/// assert_eq!(pr_str(&value, false), "\"hello, world!\"");
 ///
 /// let value = Value::Atom(Atom::String("hello, world!".to_owned()));
-/// assert_eq!(pr_str(value, true), "hello, world!");
+/// assert_eq!(pr_str(&value, true), "hello, world!");
 ///
 /// let value = Value::Atom(Atom::Int(42));
-/// assert_eq!(pr_str(value, false), "42");
+/// assert_eq!(pr_str(&value, false), "42");
 ///
 /// let value = Value::Atom(Atom::Nil);
-/// assert_eq!(pr_str(value, true), "nil");
+/// assert_eq!(pr_str(&value, true), "nil");
 ///
 /// let value = Value::Atom(Atom::True);
-/// assert_eq!(pr_str(value, true), "true");
+/// assert_eq!(pr_str(&value, true), "true");
 ///
 /// let value = Value::Atom(Atom::False);
-/// assert_eq!(pr_str(value, true), "false");
+/// assert_eq!(pr_str(&value, true), "false");
 /// ```
-pub fn pr_str(value: Value, pretty: bool) -> String {
+pub fn pr_str(value: &Value, pretty: bool) -> String {
+    pr_str_styled(value, pretty, false)
+}
+
+/// Format a mal value as a string, optionally colored with ANSI escape codes.
+///
+/// With `color` unset the output is identical to [pr_str]. With it set,
+/// strings, keywords, numbers, constants, and delimiters are each given a
+/// distinct color, so the output is meant for a terminal rather than for
+/// reading back in.
+///
+/// # Examples
+///
+/// ```
+/// use mal::types::{Value, Atom};
+/// use mal::printer::pr_str_styled;
+///
+/// let value = Value::Atom(Atom::Int(42));
+/// assert_eq!(pr_str_styled(&value, false, false), "42");
+/// assert_eq!(pr_str_styled(&value, false, true), "\x1b[33m42\x1b[0m");
+/// ```
+pub fn pr_str_styled(value: &Value, pretty: bool, color: bool) -> String {
     match value {
         Value::Atom(atom) => match atom {
-            Atom::Symbol(sym) => sym,
-            Atom::Keyword(keyword) => format!(":{keyword}"),
+            Atom::Symbol(sym) => sym.to_string(),
+            Atom::Keyword(keyword) => paint(format!(":{keyword}"), Style::Keyword, color),
             Atom::String(string) => {
                 if pretty {
-                    string
+                    string.clone()
+                } else {
+                    paint(
+                        format!("\"{}\"", escape_string(string)),
+                        Style::String,
+                        color,
+                    )
+                }
+            }
+            Atom::Char(c) => {
+                if pretty {
+                    c.to_string()
                 } else {
-                    format!("\"{}\"", escape_string(&string))
+                    paint(escape_char(*c), Style::String, color)
                 }
             }
-            Atom::Int(int) => format!("{int}"),
-            Atom::Nil => "nil".to_owned(),
-            Atom::True => "true".to_owned(),
-            Atom::False => "false".to_owned(),
+            Atom::Int(int) => paint(format!("{int}"), Style::Number, color),
+            Atom::Float(float) => paint(format_float(float.0), Style::Number, color),
+            Atom::Nil => paint("nil".to_owned(), Style::Constant, color),
+            Atom::True => paint("true".to_owned(), Style::Constant, color),
+            Atom::False => paint("false".to_owned(), Style::Constant, color),
         },
-        Value::List(items) => format!("({})", pr_list_items(items)),
-        Value::Vector(items) => format!("[{}]", pr_list_items(items)),
-        Value::HashMap(map) => format!("{{{}}}", {
+        Value::List(items) => delimit("(", pr_list_items(items.iter(), pretty, color), ")", color),
+        Value::Vector(items) => {
+            delimit("[", pr_list_items(items.iter(), pretty, color), "]", color)
+        }
+        Value::Pair(pair) => {
+            let mut items = vec![&pair.0];
+            let mut tail = &pair.1;
+            while let Value::Pair(pair) = tail {
+                items.push(&pair.0);
+                tail = &pair.1;
+            }
+            let inner = format!(
+                "{} {} {}",
+                pr_list_items(items, pretty, color),
+                paint(".".to_owned(), Style::Delimiter, color),
+                pr_str_styled(tail, pretty, color)
+            );
+            delimit("(", inner, ")", color)
+        }
+        Value::HashMap(map) => {
             let mut items = Vec::with_capacity(map.len() * 2);
-            for (k, v) in map.into_iter() {
-                items.push(Value::Atom(k));
-                items.push(v);
+            for (k, v) in map {
+                items.push(Value::Atom(k.clone()));
+                items.push(v.clone());
             }
-            pr_list_items(items)
-        }),
+            delimit("{", pr_list_items(&items, pretty, color), "}", color)
+        }
+        Value::Function(_) => "#<function>".to_owned(),
+        Value::Ref(value) => delimit(
+            "(",
+            format!("atom {}", pr_str_styled(&value.borrow(), pretty, color)),
+            ")",
+            color,
+        ),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
-
-    use super::{pr_str, Atom, Value};
+    use super::{auto_color, pr_str, pr_str_styled, Atom, ColorMode, Value};
+    use crate::{core::default_env, eval::eval, map::Map, reader::read_str};
 
     #[test]
     fn test_pr_symbol() {
-        let result = pr_str(Value::Atom(Atom::Symbol("test".to_owned())), false);
+        let result = pr_str(&Value::Atom(Atom::Symbol("test".into())), false);
         assert_eq!(result, "test");
     }
 
     #[test]
     fn test_pr_string() {
-        let result = pr_str(Value::Atom(Atom::String("test".to_owned())), false);
+        let result = pr_str(&Value::Atom(Atom::String("test".to_owned())), false);
         assert_eq!(result, "\"test\"");
     }
 
     #[test]
     fn test_pr_escaped_string() {
         let result = pr_str(
-            Value::Atom(Atom::String("hello \\ escaped \" world\n".to_owned())),
+            &Value::Atom(Atom::String("hello \\ escaped \" world\n".to_owned())),
             false,
         );
         assert_eq!(result, "\"hello \\\\ escaped \\\" world\\n\"");
@@ -119,14 +305,80 @@ mod tests {
 
     #[test]
     fn test_pr_string_pretty() {
-        let result = pr_str(Value::Atom(Atom::String("test".to_owned())), true);
+        let result = pr_str(&Value::Atom(Atom::String("test".to_owned())), true);
         assert_eq!(result, "test");
     }
 
+    #[test]
+    fn test_pr_control_characters_round_trip() {
+        let value = Value::Atom(Atom::String("nul\0 bell\x07 tab\t".to_owned()));
+        let printed = pr_str(&value, false);
+        assert_eq!(printed, "\"nul\\0 bell\\x07 tab\\x09\"");
+        assert_eq!(read_str(&printed), Ok(value));
+    }
+
+    #[test]
+    fn test_pr_keyword() {
+        let value = Value::Atom(Atom::Keyword("a".to_owned()));
+        assert_eq!(pr_str(&value, false), ":a");
+        assert_eq!(pr_str(&value, true), ":a");
+        let map = read_str("{:a :b}").unwrap();
+        assert_eq!(pr_str(&map, false), "{:a :b}");
+        assert_eq!(pr_str(&map, true), "{:a :b}");
+    }
+
+    #[test]
+    fn test_pr_non_finite_floats() {
+        for pretty in [false, true] {
+            assert_eq!(pr_str(&Value::from(f64::INFINITY), pretty), "##Inf");
+            assert_eq!(pr_str(&Value::from(f64::NEG_INFINITY), pretty), "##-Inf");
+            assert_eq!(pr_str(&Value::from(f64::NAN), pretty), "##NaN");
+        }
+        assert_eq!(pr_str(&Value::from(-1e300), false), "-1e300");
+    }
+
+    #[test]
+    fn test_read_print_round_trip() {
+        let inputs = [
+            "()",
+            "(1 -2 +3 (nested (deeper)) [])",
+            "[1 [2 [3]] (4)]",
+            "{}",
+            "{:z 1 :a [2 3] \"k\" {:inner nil}}",
+            "{\"with \\\"quotes\\\"\" 1 \\c 2 3 4}",
+            ":keyword",
+            "[:a :b/c]",
+            "\"line\\nbreak \\\\ back \\\"quote\\\"\"",
+            "\"nul \\0 bell \\x07 tab \\x09 unicode é\"",
+            "#\"raw \\ string \"quoted\"\"#",
+            "(true false nil)",
+            "[1.5 -0.0 1e300 2.5e-8]",
+            "[##Inf ##-Inf ##NaN]",
+            "(\\a \\space \\newline \\( \\\\ \\; \\λ \\u0001 \\u002c \\u00a0)",
+            "(a b . c)",
+            "(symbol with-dash *star* ->arrow)",
+            "'(quote x)",
+            "`(a ~b ~@c)",
+            "^{:meta 1} [1]",
+            "@a",
+        ];
+        for input in inputs {
+            let value = read_str(input).unwrap();
+            let printed = pr_str(&value, false);
+            assert_eq!(
+                read_str(&printed),
+                Ok(value),
+                "{input} printed as {printed}"
+            );
+            let reprinted = pr_str(&read_str(&printed).unwrap(), false);
+            assert_eq!(reprinted, printed, "{input}");
+        }
+    }
+
     #[test]
     fn test_pr_escaped_string_pretty() {
         let result = pr_str(
-            Value::Atom(Atom::String("hello \\ escaped \" world\n".to_owned())),
+            &Value::Atom(Atom::String("hello \\ escaped \" world\n".to_owned())),
             true,
         );
         assert_eq!(result, "hello \\ escaped \" world\n");
@@ -134,16 +386,16 @@ mod tests {
 
     #[test]
     fn test_pr_int() {
-        let result = pr_str(Value::Atom(Atom::Int(42)), false);
+        let result = pr_str(&Value::Atom(Atom::Int(42)), false);
         assert_eq!(result, "42");
     }
 
     #[test]
     fn test_pr_list() {
         let result = pr_str(
-            Value::List(vec![
+            &Value::list(vec![
                 Value::Atom(Atom::Int(42)),
-                Value::Atom(Atom::Symbol("test".to_owned())),
+                Value::Atom(Atom::Symbol("test".into())),
             ]),
             false,
         );
@@ -153,9 +405,9 @@ mod tests {
     #[test]
     fn test_pr_vector() {
         let result = pr_str(
-            Value::Vector(vec![
+            &Value::vector(vec![
                 Value::Atom(Atom::Int(42)),
-                Value::Atom(Atom::Symbol("test".to_owned())),
+                Value::Atom(Atom::Symbol("test".into())),
             ]),
             false,
         );
@@ -165,30 +417,167 @@ mod tests {
     #[test]
     fn test_pr_hash_map() {
         let result = pr_str(
-            Value::HashMap(HashMap::from([(
+            &Value::HashMap(Map::from([(
                 Atom::Int(42),
-                Value::Atom(Atom::Symbol("test".to_owned())),
+                Value::Atom(Atom::Symbol("test".into())),
             )])),
             false,
         );
         assert_eq!(result, "{42 test}");
     }
 
+    #[test]
+    fn test_pr_hash_map_escapes_string_keys() {
+        let value = Value::HashMap(Map::from([(
+            Atom::String("a\nb \"q\"".to_owned()),
+            Value::from("c\td"),
+        )]));
+        let printed = pr_str(&value, false);
+        assert_eq!(printed, "{\"a\\nb \\\"q\\\"\" \"c\\x09d\"}");
+        assert_eq!(read_str(&printed), Ok(value.clone()));
+        assert_eq!(pr_str(&value, true), "{a\nb \"q\" c\td}");
+    }
+
+    #[test]
+    fn test_pr_empty_collections() {
+        for pretty in [false, true] {
+            assert_eq!(pr_str(&Value::list(vec![]), pretty), "()");
+            assert_eq!(pr_str(&Value::vector(vec![]), pretty), "[]");
+            assert_eq!(pr_str(&Value::HashMap(Map::new()), pretty), "{}");
+        }
+    }
+
+    #[test]
+    fn test_pr_nested_pretty() {
+        let value = read_str("(1 \"a\" :b [\"c\" {\"k\" \\d}] (\"e\" . \"f\"))").unwrap();
+        assert_eq!(pr_str(&value, true), "(1 a :b [c {k d}] (e . f))");
+        assert_eq!(
+            pr_str(&value, false),
+            "(1 \"a\" :b [\"c\" {\"k\" \\d}] (\"e\" . \"f\"))"
+        );
+    }
+
     #[test]
     fn test_pr_nil() {
-        let result = pr_str(Value::Atom(Atom::Nil), false);
+        let result = pr_str(&Value::Atom(Atom::Nil), false);
         assert_eq!(result, "nil");
     }
 
     #[test]
     fn test_pr_true() {
-        let result = pr_str(Value::Atom(Atom::True), false);
+        let result = pr_str(&Value::Atom(Atom::True), false);
         assert_eq!(result, "true");
     }
 
     #[test]
     fn test_pr_false() {
-        let result = pr_str(Value::Atom(Atom::False), false);
+        let result = pr_str(&Value::Atom(Atom::False), false);
         assert_eq!(result, "false");
     }
+
+    #[test]
+    fn test_pr_pair() {
+        let result = pr_str(
+            &Value::dotted(
+                vec![Value::Atom(Atom::Int(1)), Value::Atom(Atom::Int(2))],
+                Value::Atom(Atom::Symbol("rest".into())),
+            ),
+            false,
+        );
+        assert_eq!(result, "(1 2 . rest)");
+    }
+
+    #[test]
+    fn test_pr_char() {
+        assert_eq!(pr_str(&Value::Atom(Atom::Char('a')), false), r"\a");
+        assert_eq!(pr_str(&Value::Atom(Atom::Char('a')), true), "a");
+        assert_eq!(pr_str(&Value::Atom(Atom::Char('\n')), false), r"\newline");
+        assert_eq!(pr_str(&Value::Atom(Atom::Char('\u{7}')), false), r"\u0007");
+    }
+
+    #[test]
+    fn test_char_round_trip() {
+        for input in [r"\a", r"\newline", r"\space", r"\é"] {
+            let value = read_str(input).unwrap();
+            assert_eq!(pr_str(&value, false), input);
+        }
+        assert_eq!(pr_str(&read_str(r"\u0041").unwrap(), false), r"\A");
+    }
+
+    #[test]
+    fn test_color_never_matches_pr_str() {
+        let value = read_str(r#"(a :b "c\n" \d 1 nil [true false] {:k (x . y)})"#).unwrap();
+        assert!(!ColorMode::Never.enabled());
+        for pretty in [false, true] {
+            assert_eq!(
+                pr_str_styled(&value, pretty, ColorMode::Never.enabled()),
+                pr_str(&value, pretty)
+            );
+        }
+    }
+
+    #[test]
+    fn test_color_always() {
+        let value = read_str("(:a \"b\")").unwrap();
+        assert_eq!(
+            pr_str_styled(&value, false, ColorMode::Always.enabled()),
+            "\x1b[34m(\x1b[0m\x1b[35m:a\x1b[0m \x1b[32m\"b\"\x1b[0m\x1b[34m)\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_auto_color() {
+        let var = |value| Some(std::ffi::OsStr::new(value));
+        assert!(auto_color(true, None, None));
+        assert!(auto_color(true, None, var("xterm-256color")));
+        assert!(!auto_color(false, None, var("xterm-256color")));
+        assert!(!auto_color(true, var("1"), var("xterm-256color")));
+        assert!(auto_color(true, var(""), var("xterm-256color")));
+        assert!(!auto_color(true, None, var("dumb")));
+        assert!(!auto_color(true, var(""), var("dumb")));
+    }
+
+    #[test]
+    fn test_color_mode_from_str() {
+        assert_eq!("auto".parse(), Ok(ColorMode::Auto));
+        assert_eq!("never".parse(), Ok(ColorMode::Never));
+        assert!("sometimes".parse::<ColorMode>().is_err());
+    }
+
+    #[test]
+    fn test_pr_raw_string() {
+        let value = read_str(r##"#"C:\dir "q""#"##).unwrap();
+        assert_eq!(pr_str(&value, false), r#""C:\\dir \"q\"""#);
+        assert_eq!(pr_str(&value, true), r#"C:\dir "q""#);
+        assert_eq!(read_str(&pr_str(&value, false)), Ok(value));
+    }
+
+    #[test]
+    fn test_pr_ref_and_function() {
+        let env = default_env();
+        let value = eval(
+            read_str("(list (atom \"x\") + {:f (fn* () 1)} (atom (atom [nil])))").unwrap(),
+            &env,
+        )
+        .unwrap();
+        assert_eq!(
+            pr_str(&value, false),
+            "((atom \"x\") #<function> {:f #<function>} (atom (atom [nil])))"
+        );
+        assert_eq!(
+            pr_str(&Value::reference(Value::from("x")), true),
+            "(atom x)"
+        );
+    }
+
+    #[test]
+    fn test_pr_float() {
+        for (input, expected) in [("1.5", "1.5"), ("2.", "2.0"), ("-1e3", "-1000.0")] {
+            assert_eq!(pr_str(&read_str(input).unwrap(), false), expected);
+        }
+        assert_eq!(
+            read_str(&pr_str(&Value::from(0.1), false)),
+            Ok(Value::from(0.1))
+        );
+    }
 }
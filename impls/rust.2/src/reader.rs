@@ -1,15 +1,21 @@
 //! Turning token streams into syntax trees.
 use std::{
-    collections::HashMap,
     fmt::{self, Display, Formatter},
+    iter::Peekable,
+    ops::Range,
+    vec,
 };
 
 use crate::{
-    parser::{tokenize, ParseError, Token},
+    map::Map,
+    parser::{line_col, tokenize_spanned, tokenize_spanned_partial, ParseError, Token},
+    printer::pr_str,
     types::{Atom, Value},
 };
 
 /// Errors that can be raised while reading.
+///
+/// Positions are byte offsets into the input string.
 #[derive(Debug, PartialEq, Eq)]
 pub enum ReadError {
     /// A token was encountered that was not expected, for example a closing
@@ -24,8 +30,13 @@ pub enum ReadError {
     UnexpectedEndOfInput(usize),
     /// A hash-map was encountered with an unhashable type as a key.
     UnhashableType(Value, usize),
+    /// A hash-map literal was encountered with the same key more than once.
+    DuplicateKey(Atom, usize),
     /// A hash-map was encountered with an odd number of items.
     UnevenHashMap(usize),
+    /// A `.` was encountered in a list somewhere other than just before the
+    /// last element.
+    MisplacedDot(usize),
     /// An error occurred while parsing the input string.
     Parse(ParseError),
 
@@ -49,34 +60,112 @@ impl Display for ReadError {
             ReadError::UnhashableType(value, pos) => {
                 write!(f, "unhashable type {} at position {pos}", value.type_name())
             }
+            ReadError::DuplicateKey(key, pos) => {
+                let key = pr_str(&Value::Atom(key.clone()), false);
+                write!(f, "duplicate key {key} in hashmap at position {pos}")
+            }
             ReadError::UnevenHashMap(pos) => {
                 write!(f, "odd number of elements for hashmap at position {pos}")
             }
+            ReadError::MisplacedDot(pos) => write!(f, "misplaced '.' at position {pos}"),
             ReadError::Parse(error) => write!(f, "{error}"),
             ReadError::NoInput => Ok(()),
         }
     }
 }
 
+impl ReadError {
+    /// The byte offset in the input at which the error occurred, if there is
+    /// one.
+    pub fn pos(&self) -> Option<usize> {
+        match self {
+            ReadError::UnexpectedToken { pos, .. }
+            | ReadError::UnexpectedEndOfInput(pos)
+            | ReadError::UnhashableType(_, pos)
+            | ReadError::DuplicateKey(_, pos)
+            | ReadError::UnevenHashMap(pos)
+            | ReadError::MisplacedDot(pos) => Some(*pos),
+            ReadError::Parse(error) => Some(error.pos()),
+            ReadError::NoInput => None,
+        }
+    }
+
+    /// The line and column in `input` at which the error occurred, if it has a
+    /// position. `input` should be the text that was being read.
+    pub fn line_col(&self, input: &str) -> Option<(usize, usize)> {
+        self.pos().map(|pos| line_col(input, pos))
+    }
+
+    /// A short identifier for the kind of error, such as `"unhashable-key"`.
+    ///
+    /// Unlike the [Display] output, codes are stable and can be matched on.
+    /// Errors from the parser have the code of the [ParseError] they wrap.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ReadError::UnexpectedToken { .. } => "unexpected-token",
+            ReadError::UnexpectedEndOfInput(_) => "unexpected-eof",
+            ReadError::UnhashableType(..) => "unhashable-key",
+            ReadError::DuplicateKey(..) => "duplicate-key",
+            ReadError::UnevenHashMap(_) => "uneven-hashmap",
+            ReadError::MisplacedDot(_) => "misplaced-dot",
+            ReadError::Parse(error) => error.code(),
+            ReadError::NoInput => "no-input",
+        }
+    }
+}
+
+/// Returns true if `error` means the input ended before a complete form was
+/// read, for example an unclosed list or string.
+///
+/// Such input may become valid once more text is appended to it.
+pub fn is_incomplete(error: &ReadError) -> bool {
+    matches!(
+        error,
+        ReadError::UnexpectedEndOfInput(_)
+            | ReadError::Parse(
+                ParseError::UnexpectedEndOfInput(_) | ParseError::UnterminatedString(_)
+            )
+    )
+}
+
 pub(crate) struct Reader {
-    tokens: Vec<Token>,
-    pos: usize,
+    tokens: Peekable<vec::IntoIter<(Token, Range<usize>)>>,
+    /// Length of the input, reported as the position of unexpected ends.
+    end: usize,
+    /// The position just past the last token taken from the stream.
+    consumed: usize,
 }
 
 impl Reader {
-    fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, pos: 0 }
+    fn new(tokens: Vec<(Token, Range<usize>)>, end: usize) -> Self {
+        Self {
+            tokens: tokens.into_iter().peekable(),
+            end,
+            consumed: 0,
+        }
     }
 
-    fn peek(&self) -> Option<Token> {
-        if self.pos < self.tokens.len() {
-            Some(self.tokens[self.pos].clone())
-        } else {
-            None
+    fn peek(&mut self) -> Option<&Token> {
+        self.tokens.peek().map(|(token, _)| token)
+    }
+
+    /// The position in the input of the next token, or of the end of the input
+    /// if there are no tokens left.
+    fn pos(&mut self) -> usize {
+        self.tokens.peek().map_or(self.end, |(_, span)| span.start)
+    }
+
+    /// Skip any `#_` tokens along with the form that follows each of them.
+    fn skip_discarded(&mut self) -> Result<(), ReadError> {
+        while self.peek() == Some(&Token::Discard) {
+            self.next();
+            self.read_form()?;
         }
+        Ok(())
     }
 
     fn read_form(&mut self) -> Result<Value, ReadError> {
+        self.skip_discarded()?;
         match self.peek() {
             Some(Token::LParen) => {
                 self.next();
@@ -87,37 +176,77 @@ impl Reader {
                 self.read_vector()
             }
             Some(Token::LBrace) => {
+                let start = self.pos();
                 self.next();
-                self.read_map()
+                self.read_map(start)
             }
             Some(_) => self.read_atom(),
-            None => Err(ReadError::UnexpectedEndOfInput(self.pos)),
+            None => Err(ReadError::UnexpectedEndOfInput(self.end)),
         }
     }
 
     fn read_list(&mut self) -> Result<Value, ReadError> {
-        self.read_list_items(Token::RParen).map(Value::List)
+        let mut items = vec![];
+        loop {
+            self.skip_discarded()?;
+            let pos = self.pos();
+            match self.peek() {
+                Some(Token::RParen) => {
+                    self.next();
+                    break Ok(Value::list(items));
+                }
+                Some(Token::Symbol(sym)) if sym == "." => {
+                    self.next();
+                    break self.read_dotted_tail(items, pos);
+                }
+                Some(_) => items.push(self.read_form()?),
+                None => break Err(ReadError::UnexpectedEndOfInput(self.end)),
+            }
+        }
+    }
+
+    /// Read the single form following a `.` at `dot` and the closing paren
+    /// after it.
+    fn read_dotted_tail(&mut self, items: Vec<Value>, dot: usize) -> Result<Value, ReadError> {
+        self.skip_discarded()?;
+        if items.is_empty() || self.peek() == Some(&Token::RParen) {
+            return Err(ReadError::MisplacedDot(dot));
+        }
+        let tail = self.read_form()?;
+        self.skip_discarded()?;
+        match self.next() {
+            Some(Token::RParen) => Ok(Value::dotted(items, tail)),
+            Some(_) => Err(ReadError::MisplacedDot(dot)),
+            None => Err(ReadError::UnexpectedEndOfInput(self.end)),
+        }
     }
 
     fn read_vector(&mut self) -> Result<Value, ReadError> {
-        self.read_list_items(Token::RBracket).map(Value::Vector)
+        self.read_list_items(Token::RBracket).map(Value::vector)
     }
 
-    fn read_map(&mut self) -> Result<Value, ReadError> {
+    /// Read a hash-map literal. Keys follow the rules described on [Map].
+    fn read_map(&mut self, start: usize) -> Result<Value, ReadError> {
         let mut items = self.read_list_items(Token::RBrace)?.into_iter();
-        let mut map = HashMap::new();
+        let mut map = Map::new();
 
         while let Some(k) = items.next() {
             if let Some(v) = items.next() {
                 let k = match k {
                     Value::Atom(atom) => Ok(atom),
-                    Value::List(_) | Value::Vector(_) | Value::HashMap(_) => {
-                        Err(ReadError::UnhashableType(k, self.pos))
-                    }
+                    Value::List(_)
+                    | Value::Vector(_)
+                    | Value::Pair(_)
+                    | Value::HashMap(_)
+                    | Value::Function(_)
+                    | Value::Ref(_) => Err(ReadError::UnhashableType(k, start)),
                 }?;
+                if map.contains_key(&k) {
+                    return Err(ReadError::DuplicateKey(k, start));
+                }
                 map.insert(k, v);
             } else {
-                return Err(ReadError::UnevenHashMap(self.pos));
+                return Err(ReadError::UnevenHashMap(start));
             }
         }
 
@@ -127,60 +256,64 @@ impl Reader {
     fn read_list_items(&mut self, terminator: Token) -> Result<Vec<Value>, ReadError> {
         let mut result = vec![];
         loop {
+            self.skip_discarded()?;
             match self.peek() {
-                Some(t) if t == terminator => {
+                Some(t) if *t == terminator => {
                     self.next();
                     break Ok(result);
                 }
                 Some(_) => result.push(self.read_form()?),
-                None => break Err(ReadError::UnexpectedEndOfInput(self.pos)),
+                None => break Err(ReadError::UnexpectedEndOfInput(self.end)),
             }
         }
     }
 
     fn read_atom(&mut self) -> Result<Value, ReadError> {
+        let pos = self.pos();
         match self.next() {
-            Some(Token::Quote) => Ok(Value::List(vec![
-                Value::Atom(Atom::Symbol("quote".to_string())),
+            Some(Token::Quote) => Ok(Value::list(vec![
+                Value::Atom(Atom::Symbol("quote".into())),
                 self.read_form()?,
             ])),
-            Some(Token::Quasiquote) => Ok(Value::List(vec![
-                Value::Atom(Atom::Symbol("quasiquote".to_string())),
+            Some(Token::Quasiquote) => Ok(Value::list(vec![
+                Value::Atom(Atom::Symbol("quasiquote".into())),
                 self.read_form()?,
             ])),
-            Some(Token::Unquote) => Ok(Value::List(vec![
-                Value::Atom(Atom::Symbol("unquote".to_string())),
+            Some(Token::Unquote) => Ok(Value::list(vec![
+                Value::Atom(Atom::Symbol("unquote".into())),
                 self.read_form()?,
             ])),
-            Some(Token::SpliceUnquote) => Ok(Value::List(vec![
-                Value::Atom(Atom::Symbol("splice-unquote".to_string())),
+            Some(Token::SpliceUnquote) => Ok(Value::list(vec![
+                Value::Atom(Atom::Symbol("splice-unquote".into())),
                 self.read_form()?,
             ])),
-            Some(Token::Deref) => Ok(Value::List(vec![
-                Value::Atom(Atom::Symbol("deref".to_string())),
+            Some(Token::Deref) => Ok(Value::list(vec![
+                Value::Atom(Atom::Symbol("deref".into())),
                 self.read_form()?,
             ])),
             Some(Token::WithMeta) => {
                 let metadata = self.read_form()?;
-                Ok(Value::List(vec![
-                    Value::Atom(Atom::Symbol("with-meta".to_string())),
+                Ok(Value::list(vec![
+                    Value::Atom(Atom::Symbol("with-meta".into())),
                     self.read_form()?,
                     metadata,
                 ]))
             }
-            Some(Token::Symbol(sym)) => Ok(Value::Atom(Atom::Symbol(sym))),
+            Some(Token::Symbol(sym)) => Ok(Value::Atom(Atom::Symbol(sym.into()))),
             Some(Token::Keyword(keyword)) => Ok(Value::Atom(Atom::Keyword(keyword))),
             Some(Token::String(string)) => Ok(Value::Atom(Atom::String(string))),
+            Some(Token::Char(c)) => Ok(Value::Atom(Atom::Char(c))),
             Some(Token::Int(int)) => Ok(Value::Atom(Atom::Int(int))),
+            Some(Token::Float(float)) => Ok(Value::Atom(Atom::Float(float))),
             Some(Token::Nil) => Ok(Value::Atom(Atom::Nil)),
             Some(Token::True) => Ok(Value::Atom(Atom::True)),
             Some(Token::False) => Ok(Value::Atom(Atom::False)),
             Some(t) => Err(ReadError::UnexpectedToken {
                 got: t,
                 expected: None,
-                pos: self.pos,
+                pos,
             }),
-            None => Err(ReadError::UnexpectedEndOfInput(self.pos)),
+            None => Err(ReadError::UnexpectedEndOfInput(self.end)),
         }
     }
 }
@@ -189,11 +322,9 @@ impl Iterator for Reader {
     type Item = Token;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let token = self.peek();
-        if token.is_some() {
-            self.pos += 1;
-        }
-        token
+        let (token, span) = self.tokens.next()?;
+        self.consumed = span.end;
+        Some(token)
     }
 }
 
@@ -215,38 +346,186 @@ impl Iterator for Reader {
 /// let value = read_str("(42 6502)").unwrap();
 /// assert_eq!(
 ///     value,
-///     Value::List(vec![
+///     Value::list(vec![
 ///         Value::Atom(Atom::Int(42)),
 ///         Value::Atom(Atom::Int(6502))
 ///     ])
 /// );
 /// ```
 pub fn read_str(input: &str) -> Result<Value, ReadError> {
-    let tokens = tokenize(input).map_err(ReadError::Parse)?;
-    if tokens.is_empty() {
+    let tokens = tokenize_spanned(input).map_err(ReadError::Parse)?;
+    let mut reader = Reader::new(tokens, input.len());
+    reader.skip_discarded()?;
+    if reader.peek().is_none() {
         return Err(ReadError::NoInput);
     }
 
-    Reader::new(tokens).read_form()
+    reader.read_form()
+}
+
+/// Read the first form in `input`, returning it along with the byte offset
+/// just past the end of it.
+///
+/// Slicing `input` at the offset gives the rest of the input, from which the
+/// next form can be read in turn. Only as much of `input` as the first form
+/// needs has to be valid, so a syntax error further on is reported only when
+/// reading reaches it.
+///
+/// # Examples
+///
+/// ```
+/// use mal::types::Value;
+/// use mal::reader::read_form_from;
+///
+/// let input = "(+ 1 2) :next";
+/// let (value, end) = read_form_from(input).unwrap();
+/// assert_eq!(end, 7);
+/// assert_eq!(&input[end..], " :next");
+/// ```
+pub fn read_form_from(input: &str) -> Result<(Value, usize), ReadError> {
+    let (tokens, parse_error) = tokenize_spanned_partial(input);
+    let mut reader = Reader::new(tokens, input.len());
+    let result = reader.skip_discarded().and_then(|()| match reader.peek() {
+        Some(_) => reader.read_form(),
+        None => Err(ReadError::NoInput),
+    });
+    match (result, parse_error) {
+        (Ok(value), _) => Ok((value, reader.consumed)),
+        // Reading ran off the end of the valid tokens, so the form was cut
+        // short by the syntax error rather than by the end of the input.
+        (Err(ReadError::UnexpectedEndOfInput(_) | ReadError::NoInput), Some(error)) => {
+            Err(ReadError::Parse(error))
+        }
+        (Err(error), _) => Err(error),
+    }
+}
+
+/// Interpret a string into a sequence of mal values.
+///
+/// Unlike [read_str](crate::reader::read_str), which only reads the first
+/// form in `input`, every top-level form is read and returned in order. Input
+/// containing no forms at all produces an empty vector.
+///
+/// # Arguments
+///
+/// * `input` - A string slice containing some mal source code.
+///
+/// # Examples
+///
+/// ```
+/// use mal::types::{Atom, Value};
+/// use mal::reader::read_str_all;
+///
+/// let values = read_str_all("42 :answer").unwrap();
+/// assert_eq!(
+///     values,
+///     vec![
+///         Value::Atom(Atom::Int(42)),
+///         Value::Atom(Atom::Keyword("answer".to_owned()))
+///     ]
+/// );
+/// ```
+pub fn read_str_all(input: &str) -> Result<Vec<Value>, ReadError> {
+    let tokens = tokenize_spanned(input).map_err(ReadError::Parse)?;
+    let mut reader = Reader::new(tokens, input.len());
+    let mut values = vec![];
+    reader.skip_discarded()?;
+    while reader.peek().is_some() {
+        values.push(reader.read_form()?);
+        reader.skip_discarded()?;
+    }
+    Ok(values)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{read_str, Atom, ReadError, Value};
+    use super::{
+        is_incomplete, read_form_from, read_str, read_str_all, Atom, ReadError, Token, Value,
+    };
+    use crate::parser::ParseError;
+
+    #[test]
+    fn test_error_line_after_comment() {
+        let input = "(def! x 1) ; a comment\n(+ x ]";
+        let error = read_str_all(input).unwrap_err();
+        assert_eq!(error.line_col(input), Some((2, 6)));
+
+        let input = "; one\n;; two\n\n  \"unterminated";
+        let error = read_str(input).unwrap_err();
+        assert_eq!(error.line_col(input), Some((4, 3)));
+
+        let input = "(1 ; no newline before the end";
+        let error = read_str(input).unwrap_err();
+        assert_eq!(error.line_col(input), Some((1, 31)));
+        assert_eq!(ReadError::NoInput.line_col(""), None);
+    }
+
+    #[test]
+    fn test_read_form_from() {
+        let mut input = "(a (b)) #_skipped [1 2] ; trailing comment\n";
+        let mut forms = vec![];
+        loop {
+            match read_form_from(input) {
+                Ok((value, end)) => {
+                    forms.push(value);
+                    input = &input[end..];
+                }
+                Err(ReadError::NoInput) => break,
+                Err(error) => panic!("unexpected error {error}"),
+            }
+        }
+        assert_eq!(forms, read_str_all("(a (b)) [1 2]").unwrap());
+        assert_eq!(read_form_from("  'x y"), Ok((read_str("'x").unwrap(), 4)));
+    }
+
+    #[test]
+    fn test_read_form_from_errors() {
+        // An error after the first form does not stop it being read.
+        let input = "(1) \"unterminated";
+        let (value, end) = read_form_from(input).unwrap();
+        assert_eq!(value, read_str("(1)").unwrap());
+        assert_eq!(
+            read_form_from(&input[end..]),
+            Err(ReadError::Parse(ParseError::UnterminatedString(1)))
+        );
+        assert_eq!(
+            read_form_from("(1 \"x"),
+            Err(ReadError::Parse(ParseError::UnterminatedString(3)))
+        );
+        assert_eq!(
+            read_form_from("(1"),
+            Err(ReadError::UnexpectedEndOfInput(2))
+        );
+        assert_eq!(read_form_from(" ; nothing"), Err(ReadError::NoInput));
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let code = |input| read_str(input).unwrap_err().code();
+        assert_eq!(code(")"), "unexpected-token");
+        assert_eq!(code("(1 2"), "unexpected-eof");
+        assert_eq!(code("{[1] 2}"), "unhashable-key");
+        assert_eq!(code("{:a}"), "uneven-hashmap");
+        assert_eq!(code("{:a 1 :a 2}"), "duplicate-key");
+        assert_eq!(code("(. 1)"), "misplaced-dot");
+        assert_eq!(code("\"abc"), "unterminated-string");
+        assert_eq!(code("\"\\q\""), "unknown-escape");
+        assert_eq!(code(""), "no-input");
+    }
 
     #[test]
     fn test_read_str() {
         let input = "(+ 5 :a11y nil true false (* 34 8) \"hello\")";
         let value = read_str(input).unwrap();
-        let expected = Value::List(vec![
-            Value::Atom(Atom::Symbol("+".to_owned())),
+        let expected = Value::list(vec![
+            Value::Atom(Atom::Symbol("+".into())),
             Value::Atom(Atom::Int(5)),
             Value::Atom(Atom::Keyword("a11y".to_owned())),
             Value::Atom(Atom::Nil),
             Value::Atom(Atom::True),
             Value::Atom(Atom::False),
-            Value::List(vec![
-                Value::Atom(Atom::Symbol("*".to_owned())),
+            Value::list(vec![
+                Value::Atom(Atom::Symbol("*".into())),
                 Value::Atom(Atom::Int(34)),
                 Value::Atom(Atom::Int(8)),
             ]),
@@ -255,10 +534,194 @@ mod tests {
         assert_eq!(value, expected);
     }
 
+    #[test]
+    fn test_read_signed_numbers() {
+        let value = read_str("(+ +5 -5 ++)").unwrap();
+        let expected = Value::list(vec![
+            Value::Atom(Atom::Symbol("+".into())),
+            Value::Atom(Atom::Int(5)),
+            Value::Atom(Atom::Int(-5)),
+            Value::Atom(Atom::Symbol("++".into())),
+        ]);
+        assert_eq!(value, expected);
+    }
+
     #[test]
     fn test_read_just_a_comment() {
         let input = "; this is a comment";
         let value = read_str(input);
         assert_eq!(value, Err(ReadError::NoInput));
     }
+
+    #[test]
+    fn test_read_str_all() {
+        let input = "(def! a 1) [a] ; trailing comment";
+        let values = read_str_all(input).unwrap();
+        assert_eq!(
+            values,
+            vec![
+                Value::list(vec![
+                    Value::Atom(Atom::Symbol("def!".into())),
+                    Value::Atom(Atom::Symbol("a".into())),
+                    Value::Atom(Atom::Int(1)),
+                ]),
+                Value::vector(vec![Value::Atom(Atom::Symbol("a".into()))]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_str_all_empty() {
+        assert_eq!(read_str_all(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_error_pos() {
+        assert_eq!(read_str("(1 ]").unwrap_err().pos(), Some(3));
+        assert_eq!(read_str("(1 \"a").unwrap_err().pos(), Some(3));
+        assert_eq!(read_str("{:a}").unwrap_err().pos(), Some(0));
+        assert_eq!(read_str("").unwrap_err().pos(), None);
+    }
+
+    #[test]
+    fn test_is_incomplete() {
+        for input in ["(1 2", "[(", "{:a", "\"unterminated", "'"] {
+            let error = read_str(input).unwrap_err();
+            assert!(is_incomplete(&error), "{input} should be incomplete");
+        }
+        for input in [")", "(1]", "{:a}", "\"\\q\""] {
+            let error = read_str(input).unwrap_err();
+            assert!(!is_incomplete(&error), "{input} should not be incomplete");
+        }
+    }
+
+    #[test]
+    fn test_read_large_literal_heavy_input() {
+        let string = "x".repeat(1_000);
+        let input = format!("[{}]", format!("\"{string}\" ").repeat(1_000));
+        match read_str(&input).unwrap() {
+            Value::Vector(items) => {
+                assert_eq!(items.len(), 1_000);
+                assert!(items
+                    .iter()
+                    .all(|item| *item == Value::Atom(Atom::String(string.clone()))));
+            }
+            other => panic!("expected a vector, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_empty_keyword_is_a_read_error() {
+        let error = read_str("{:a 1 : 2}").unwrap_err();
+        assert_eq!(error, ReadError::Parse(ParseError::EmptyKeyword(6)));
+        assert_eq!(error.code(), "empty-keyword");
+    }
+
+    #[test]
+    fn test_error_positions() {
+        assert_eq!(
+            read_str("(1 2 {:a 1 :b})"),
+            Err(ReadError::UnevenHashMap(5))
+        );
+        assert_eq!(
+            read_str("  {[1] 2}"),
+            Err(ReadError::UnhashableType(
+                Value::vector(vec![Value::Atom(Atom::Int(1))]),
+                2
+            ))
+        );
+        assert_eq!(
+            read_str("(\"long string\" ])"),
+            Err(ReadError::UnexpectedToken {
+                got: Token::RBracket,
+                expected: None,
+                pos: 15
+            })
+        );
+        assert_eq!(read_str("(1 2"), Err(ReadError::UnexpectedEndOfInput(4)));
+    }
+
+    #[test]
+    fn test_read_dotted_pair() {
+        let sym = |name: &str| Value::Atom(Atom::Symbol(name.into()));
+        assert_eq!(
+            read_str("(a . b)"),
+            Ok(Value::dotted(vec![sym("a")], sym("b")))
+        );
+        assert_eq!(
+            read_str("(a b . c)"),
+            Ok(Value::dotted(vec![sym("a"), sym("b")], sym("c")))
+        );
+        assert_eq!(
+            read_str("(a . (b c))"),
+            Ok(Value::list(vec![sym("a"), sym("b"), sym("c")]))
+        );
+    }
+
+    #[test]
+    fn test_misplaced_dot() {
+        assert_eq!(read_str("(. a)"), Err(ReadError::MisplacedDot(1)));
+        assert_eq!(read_str("(a .)"), Err(ReadError::MisplacedDot(3)));
+        assert_eq!(read_str("(a . b c)"), Err(ReadError::MisplacedDot(3)));
+        assert!(is_incomplete(&read_str("(a . b").unwrap_err()));
+    }
+
+    #[test]
+    fn test_discard() {
+        let ints = |ints: &[i64]| Value::list(ints.iter().map(|i| Value::from(*i)).collect());
+        assert_eq!(read_str("(1 #_2 3)"), Ok(ints(&[1, 3])));
+        assert_eq!(read_str("(1 #_ #_ 2 3 4)"), Ok(ints(&[1, 4])));
+        assert_eq!(read_str("(1 #_(2 [3]) #_4)"), Ok(ints(&[1])));
+        assert_eq!(read_str("#_1 2"), Ok(Value::from(2)));
+        assert_eq!(read_str("#_1"), Err(ReadError::NoInput));
+        assert_eq!(
+            read_str_all("1 #_2 3 #_4"),
+            Ok(vec![Value::from(1), Value::from(3)])
+        );
+        assert!(is_incomplete(&read_str("(1 #_").unwrap_err()));
+    }
+
+    #[test]
+    fn test_unhashable_type_message() {
+        let error = read_str("{(1) 2}").unwrap_err();
+        assert_eq!(error.to_string(), "unhashable type list at position 0");
+    }
+
+    #[test]
+    fn test_duplicate_key() {
+        let error = read_str("[{:a 1 :b 2 :a 3}]").unwrap_err();
+        assert_eq!(
+            error,
+            ReadError::DuplicateKey(Atom::Keyword("a".to_owned()), 1)
+        );
+        assert_eq!(
+            error.to_string(),
+            "duplicate key :a in hashmap at position 1"
+        );
+        assert_eq!(
+            read_str("{\"a\" 1 \"a\" 1}"),
+            Err(ReadError::DuplicateKey(Atom::String("a".to_owned()), 0))
+        );
+    }
+
+    #[test]
+    fn test_atom_keys() {
+        let Ok(Value::HashMap(map)) = read_str("{nil 1 true 2 false 3 0 4 sym 5}") else {
+            panic!("expected a hashmap");
+        };
+        assert_eq!(map.get(&Atom::Nil), Some(&Value::from(1)));
+        assert_eq!(map.get(&Atom::True), Some(&Value::from(2)));
+        assert_eq!(map.get(&Atom::False), Some(&Value::from(3)));
+        assert_eq!(map.get(&Atom::Int(0)), Some(&Value::from(4)));
+        assert_eq!(map.get(&Atom::Symbol("sym".into())), Some(&Value::from(5)));
+    }
+
+    #[test]
+    fn test_distinct_keys() {
+        let value = read_str("{:a 1 \"a\" 2 a 3 1 4 1.0 5}").unwrap();
+        match value {
+            Value::HashMap(map) => assert_eq!(map.len(), 5),
+            other => panic!("expected a hashmap, got {other:?}"),
+        }
+    }
 }
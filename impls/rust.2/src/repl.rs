@@ -0,0 +1,393 @@
+//! The interactive read-eval-print loop shared by the step binaries.
+use std::{
+    env,
+    error::Error,
+    ffi::OsString,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use rustyline::{error::ReadlineError, Config, Editor, Helper};
+
+use crate::{
+    eval::{clear_interrupt, interrupt},
+    reader::{is_incomplete, ReadError},
+    MalError,
+};
+
+/// The name of the history file, kept in the home directory unless
+/// `MAL_HISTFILE` says otherwise.
+pub const HISTFILE: &str = ".mal_history";
+
+/// The number of history entries kept unless `MAL_HISTSIZE` says otherwise.
+pub const HISTSIZE: usize = 1000;
+
+const PROMPT: &str = "user> ";
+const CONTINUATION_PROMPT: &str = "  ...> ";
+
+/// Settings for [run].
+pub struct Options<H: Helper> {
+    /// Where to load and save line history.
+    pub histfile: PathBuf,
+    /// The maximum number of history entries to keep.
+    pub histsize: usize,
+    /// An optional rustyline helper, for example to provide tab completion.
+    pub helper: Option<H>,
+}
+
+impl<H: Helper> Options<H> {
+    /// Create options using `helper`, with the history settings taken from the
+    /// `MAL_HISTFILE` and `MAL_HISTSIZE` environment variables.
+    pub fn new(helper: Option<H>) -> Self {
+        Self {
+            histfile: histfile_path(env::var_os("MAL_HISTFILE"), env::var_os("HOME")),
+            histsize: histsize(env::var("MAL_HISTSIZE").ok()),
+            helper,
+        }
+    }
+}
+
+impl Default for Options<()> {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+/// Resolve the history file from the values of `MAL_HISTFILE` and `HOME`.
+///
+/// `MAL_HISTFILE` is used as given if it is set and non-empty. Otherwise the
+/// history is kept in [HISTFILE] in the home directory, or in the current
+/// directory if there is no home directory.
+pub fn histfile_path(histfile: Option<OsString>, home: Option<OsString>) -> PathBuf {
+    match (histfile, home) {
+        (Some(histfile), _) if !histfile.is_empty() => histfile.into(),
+        (_, Some(home)) if !home.is_empty() => PathBuf::from(home).join(HISTFILE),
+        _ => PathBuf::from(HISTFILE),
+    }
+}
+
+/// Resolve the history size from the value of `MAL_HISTSIZE`, falling back to
+/// [HISTSIZE] if it is unset or not a number.
+pub fn histsize(histsize: Option<String>) -> usize {
+    histsize
+        .and_then(|size| size.trim().parse().ok())
+        .unwrap_or(HISTSIZE)
+}
+
+/// Render the line of `input` containing the byte offset `pos`, followed by a
+/// line with a `^` under the character at `pos`.
+///
+/// # Examples
+///
+/// ```
+/// use mal::repl::caret;
+///
+/// assert_eq!(caret("(+ 1\n   2))", 10), "   2))\n     ^");
+/// ```
+pub fn caret(input: &str, pos: usize) -> String {
+    let pos = (0..=pos.min(input.len()))
+        .rev()
+        .find(|&i| input.is_char_boundary(i))
+        .unwrap_or(0);
+    let start = input[..pos].rfind('\n').map_or(0, |i| i + 1);
+    let end = input[pos..].find('\n').map_or(input.len(), |i| pos + i);
+    let column = input[start..pos].chars().count();
+    format!("{}\n{}^", &input[start..end], " ".repeat(column))
+}
+
+/// A source of input lines for the REPL.
+///
+/// This is implemented for rustyline's [Editor], and exists so that the loop
+/// can be driven by other sources in tests.
+pub trait LineReader {
+    /// Read a line, displaying `prompt` first.
+    fn read_line(&mut self, prompt: &str) -> Result<String, ReadlineError>;
+
+    /// Record `line` in the history.
+    fn add_history(&mut self, line: &str);
+}
+
+impl<H: Helper> LineReader for Editor<H> {
+    fn read_line(&mut self, prompt: &str) -> Result<String, ReadlineError> {
+        self.readline(prompt)
+    }
+
+    fn add_history(&mut self, line: &str) {
+        self.add_history_entry(line);
+    }
+}
+
+/// Make Ctrl-C [interrupt] the evaluation in progress instead of killing the
+/// process.
+///
+/// While a line is being edited the terminal is in raw mode, so Ctrl-C is
+/// seen by the line editor rather than raised as a signal.
+#[cfg(unix)]
+fn install_interrupt_handler() {
+    extern "C" fn handle_sigint(_: libc::c_int) {
+        interrupt();
+    }
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe.
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            handle_sigint as *const () as libc::sighandler_t,
+        );
+    }
+}
+
+#[cfg(not(unix))]
+fn install_interrupt_handler() {}
+
+/// Run an interactive REPL on the terminal until end of input.
+///
+/// Each complete input entered is passed to `rep`, which decides how it is
+/// read, evaluated, and printed, and returns a result for each form it
+/// evaluated. If `rep` reports that the input is
+/// [incomplete](crate::reader::is_incomplete), more lines are read and
+/// appended to it before trying again.
+///
+/// Pressing Ctrl-C while an input is being evaluated stops it with
+/// [EvalError::Interrupted](crate::eval::EvalError::Interrupted) and returns
+/// to the prompt.
+///
+/// # Arguments
+///
+/// * `options` - History and helper settings for the line editor.
+/// * `rep` - Called with each complete input, returning the text to print for
+///   each form in it.
+pub fn run<H: Helper>(
+    options: Options<H>,
+    rep: impl FnMut(&str) -> Vec<Result<String, MalError>>,
+) -> Result<(), Box<dyn Error>> {
+    let config = Config::builder().max_history_size(options.histsize).build();
+    let mut editor = Editor::<H>::with_config(config)?;
+    editor.set_helper(options.helper);
+    editor.load_history(&options.histfile).ok();
+    install_interrupt_handler();
+
+    run_with(&mut editor, &mut io::stdout(), &mut io::stderr(), rep)?;
+
+    editor.save_history(&options.histfile)?;
+    Ok(())
+}
+
+/// Run the REPL loop, reading from `lines` and writing results to `out` and
+/// errors to `err`.
+///
+/// Each input is added to the history unless it repeats the previous entry.
+pub fn run_with(
+    lines: &mut impl LineReader,
+    out: &mut impl Write,
+    err: &mut impl Write,
+    mut rep: impl FnMut(&str) -> Vec<Result<String, MalError>>,
+) -> io::Result<()> {
+    let mut buffer = String::new();
+    let mut last_entry = None;
+    loop {
+        let prompt = if buffer.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        };
+        match lines.read_line(prompt) {
+            Ok(input) => {
+                buffer.push_str(&input);
+                buffer.push('\n');
+                let input = buffer.trim();
+                if !input.is_empty() {
+                    clear_interrupt();
+                    let results = rep(input);
+                    if let [Err(MalError::Read(error))] = results.as_slice() {
+                        if is_incomplete(error) {
+                            continue;
+                        }
+                    }
+                    for result in results {
+                        match result {
+                            Ok(output) => writeln!(out, "{output}")?,
+                            Err(MalError::Read(ReadError::NoInput)) => (),
+                            Err(MalError::Read(error)) => {
+                                writeln!(err, "error: {error}")?;
+                                if let Some(pos) = error.pos() {
+                                    writeln!(err, "{}", caret(input, pos))?;
+                                }
+                            }
+                            Err(error) => writeln!(err, "error: {error}")?,
+                        }
+                    }
+                    if last_entry.as_deref() != Some(input) {
+                        lines.add_history(input);
+                        last_entry = Some(input.to_owned());
+                    }
+                }
+                buffer.clear();
+            }
+            Err(ReadlineError::Interrupted) => buffer.clear(),
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                writeln!(err, "error: {e}")?;
+                buffer.clear();
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, ffi::OsString, path::PathBuf};
+
+    use rustyline::error::ReadlineError;
+
+    use super::{caret, histfile_path, histsize, run_with, LineReader, HISTSIZE};
+    use crate::{core::default_env, rep_all};
+
+    /// Feeds a fixed list of lines to the REPL, recording the prompts shown.
+    struct Script {
+        lines: VecDeque<&'static str>,
+        prompts: Vec<String>,
+        history: Vec<String>,
+    }
+
+    impl Script {
+        fn new(lines: &[&'static str]) -> Self {
+            Self {
+                lines: lines.iter().copied().collect(),
+                prompts: vec![],
+                history: vec![],
+            }
+        }
+    }
+
+    impl LineReader for Script {
+        fn read_line(&mut self, prompt: &str) -> Result<String, ReadlineError> {
+            self.prompts.push(prompt.to_owned());
+            self.lines
+                .pop_front()
+                .map(str::to_owned)
+                .ok_or(ReadlineError::Eof)
+        }
+
+        fn add_history(&mut self, line: &str) {
+            self.history.push(line.to_owned());
+        }
+    }
+
+    #[test]
+    fn test_run_echoes() {
+        let mut script = Script::new(&["hello", "", "  world  "]);
+        let (mut out, mut err) = (vec![], vec![]);
+        run_with(&mut script, &mut out, &mut err, |input| {
+            vec![Ok(input.to_owned())]
+        })
+        .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "hello\nworld\n");
+        assert!(err.is_empty());
+        assert_eq!(script.history, ["hello", "world"]);
+    }
+
+    #[test]
+    fn test_run_dedups_history() {
+        let mut script = Script::new(&["1", "1", "2", "1"]);
+        let (mut out, mut err) = (vec![], vec![]);
+        run_with(&mut script, &mut out, &mut err, |input| {
+            vec![Ok(input.to_owned())]
+        })
+        .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "1\n1\n2\n1\n");
+        assert_eq!(script.history, ["1", "2", "1"]);
+    }
+
+    #[test]
+    fn test_histfile_path() {
+        let os = |s: &str| Some(OsString::from(s));
+        assert_eq!(
+            histfile_path(os("/tmp/hist"), os("/home/me")),
+            PathBuf::from("/tmp/hist")
+        );
+        assert_eq!(
+            histfile_path(None, os("/home/me")),
+            PathBuf::from("/home/me/.mal_history")
+        );
+        assert_eq!(
+            histfile_path(os(""), os("/home/me")),
+            PathBuf::from("/home/me/.mal_history")
+        );
+        assert_eq!(histfile_path(None, None), PathBuf::from(".mal_history"));
+        assert_eq!(histfile_path(None, os("")), PathBuf::from(".mal_history"));
+    }
+
+    #[test]
+    fn test_histsize() {
+        assert_eq!(histsize(Some("50".to_owned())), 50);
+        assert_eq!(histsize(Some("lots".to_owned())), HISTSIZE);
+        assert_eq!(histsize(None), HISTSIZE);
+    }
+
+    #[test]
+    fn test_caret() {
+        assert_eq!(caret("(1 ))", 4), "(1 ))\n    ^");
+        assert_eq!(caret("(1", 2), "(1\n  ^");
+        assert_eq!(caret("(a\n b\n c))", 9), " c))\n   ^");
+        assert_eq!(caret("(\"é\" ))", 6), "(\"é\" ))\n     ^");
+        assert_eq!(caret("", 0), "\n^");
+    }
+
+    #[test]
+    fn test_run_shows_read_error_position() {
+        let env = default_env();
+        let mut script = Script::new(&["(list 1", "2]"]);
+        let (mut out, mut err) = (vec![], vec![]);
+        run_with(&mut script, &mut out, &mut err, |input| {
+            rep_all(input, &env)
+        })
+        .unwrap();
+        assert!(out.is_empty());
+        let err = String::from_utf8(err).unwrap();
+        let lines: Vec<_> = err.lines().collect();
+        assert_eq!(lines.len(), 3, "{err}");
+        assert!(lines[0].starts_with("error: unexpected token"));
+        assert_eq!(lines[1..], ["2]", " ^"]);
+    }
+
+    #[test]
+    fn test_run_multiple_forms_per_line() {
+        let env = default_env();
+        let mut script = Script::new(&[
+            "(def! a 1) (def! b 2)",
+            "(+ a b) (list",
+            "a)",
+            "a (undefined) b",
+        ]);
+        let (mut out, mut err) = (vec![], vec![]);
+        run_with(&mut script, &mut out, &mut err, |input| {
+            rep_all(input, &env)
+        })
+        .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "1\n2\n3\n(1)\n1\n");
+        assert_eq!(
+            String::from_utf8(err).unwrap(),
+            "error: 'undefined' not found\n"
+        );
+        assert_eq!(script.prompts[2], "  ...> ");
+    }
+
+    #[test]
+    fn test_run_multiline() {
+        let env = default_env();
+        let mut script = Script::new(&["(+ 1", "2)", "(undefined)"]);
+        let (mut out, mut err) = (vec![], vec![]);
+        run_with(&mut script, &mut out, &mut err, |input| {
+            rep_all(input, &env)
+        })
+        .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "3\n");
+        assert_eq!(
+            String::from_utf8(err).unwrap(),
+            "error: 'undefined' not found\n"
+        );
+        assert_eq!(script.prompts, ["user> ", "  ...> ", "user> ", "user> "]);
+        assert_eq!(script.history, ["(+ 1\n2)", "(undefined)"]);
+    }
+}
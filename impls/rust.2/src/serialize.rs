@@ -0,0 +1,305 @@
+//! [Serde](https://serde.rs) support for mal values.
+//!
+//! Values are encoded as follows:
+//!
+//! * `nil`, `true`, `false`, integers, and floats map to their natural equivalents.
+//! * Symbols, keywords, strings, and characters are tagged objects such as
+//!   `{"symbol": "foo"}`, `{"keyword": "bar"}`, `{"string": "baz"}`, and
+//!   `{"char": "c"}`.
+//! * Lists and vectors are tagged arrays such as `{"list": [1, 2]}` and
+//!   `{"vector": [1, 2]}`.
+//! * Dotted pairs are tagged two-element arrays such as `{"pair": [1, 2]}`.
+//! * Hash-maps are tagged objects such as `{"hashmap": {":a": 1}}`, where each
+//!   key is the readable form of the key atom as produced by
+//!   [pr_str](crate::printer::pr_str).
+//!
+//! * References are tagged objects such as `{"ref": 1}`.
+//!
+//! Functions cannot be serialized, and metadata is not preserved.
+use std::{fmt, rc::Rc};
+
+use serde::{
+    de::{self, MapAccess, SeqAccess, Visitor},
+    ser::{self, SerializeMap},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{
+    map::Map,
+    printer::pr_str,
+    reader::read_str,
+    types::{Atom, Value},
+};
+
+struct Items<'a>(&'a [Value]);
+
+impl Serialize for Items<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0)
+    }
+}
+
+struct Entries<'a>(&'a Map);
+
+impl Serialize for Entries<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            self.0
+                .iter()
+                .map(|(k, v)| (pr_str(&Value::Atom(k.clone()), false), v)),
+        )
+    }
+}
+
+fn serialize_tagged<S: Serializer, T: Serialize + ?Sized>(
+    serializer: S,
+    tag: &str,
+    value: &T,
+) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(1))?;
+    map.serialize_entry(tag, value)?;
+    map.end()
+}
+
+impl Serialize for Atom {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Atom::Symbol(sym) => serialize_tagged(serializer, "symbol", &*sym.name()),
+            Atom::Keyword(keyword) => serialize_tagged(serializer, "keyword", keyword),
+            Atom::String(string) => serialize_tagged(serializer, "string", string),
+            Atom::Char(c) => serialize_tagged(serializer, "char", c),
+            Atom::Int(int) => serializer.serialize_i64(*int),
+            Atom::Float(float) => serializer.serialize_f64(float.0),
+            Atom::Nil => serializer.serialize_unit(),
+            Atom::True => serializer.serialize_bool(true),
+            Atom::False => serializer.serialize_bool(false),
+        }
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Atom(atom) => atom.serialize(serializer),
+            Value::List(items) => serialize_tagged(serializer, "list", &Items(items)),
+            Value::Vector(items) => serialize_tagged(serializer, "vector", &Items(items)),
+            Value::Pair(pair) => serialize_tagged(serializer, "pair", &(&pair.0, &pair.1)),
+            Value::HashMap(map) => serialize_tagged(serializer, "hashmap", &Entries(map)),
+            Value::Function(_) => Err(ser::Error::custom("cannot serialize a function")),
+            Value::Ref(value) => serialize_tagged(serializer, "ref", &*value.borrow()),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct Key(Atom);
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let key = String::deserialize(deserializer)?;
+        match read_str(&key) {
+            Ok(Value::Atom(atom)) => Ok(Key(atom)),
+            Ok(other) => Err(de::Error::custom(format!(
+                "unhashable type {} as hashmap key",
+                other.type_name()
+            ))),
+            Err(error) => Err(de::Error::custom(format!(
+                "invalid hashmap key {key:?}: {error}"
+            ))),
+        }
+    }
+}
+
+struct MapEntries(Map);
+
+impl<'de> Deserialize<'de> for MapEntries {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(MapEntriesVisitor)
+    }
+}
+
+/// Reads hash-map entries in the order they appear, so that insertion order
+/// survives a round trip.
+struct MapEntriesVisitor;
+
+impl<'de> Visitor<'de> for MapEntriesVisitor {
+    type Value = MapEntries;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map of readable atoms to mal values")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut entries: A) -> Result<MapEntries, A::Error> {
+        let mut map = Map::new();
+        while let Some((Key(key), value)) = entries.next_entry()? {
+            map.insert(key, value);
+        }
+        Ok(MapEntries(map))
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a mal value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        Ok(Value::Atom(Atom::Nil))
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        self.visit_unit()
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Atom(if v { Atom::True } else { Atom::False }))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Atom(Atom::Int(v)))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+        i64::try_from(v)
+            .map(|int| Value::Atom(Atom::Int(int)))
+            .map_err(|_| de::Error::invalid_value(de::Unexpected::Unsigned(v), &self))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, _seq: A) -> Result<Value, A::Error> {
+        Err(de::Error::custom(
+            "untagged sequence, expected {\"list\": [...]} or {\"vector\": [...]}",
+        ))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let tag: String = map
+            .next_key()?
+            .ok_or_else(|| de::Error::custom("expected a tagged value"))?;
+        let value = match tag.as_str() {
+            "symbol" => Value::Atom(Atom::Symbol(map.next_value::<String>()?.into())),
+            "keyword" => Value::Atom(Atom::Keyword(map.next_value()?)),
+            "string" => Value::Atom(Atom::String(map.next_value()?)),
+            "char" => Value::Atom(Atom::Char(map.next_value()?)),
+            "list" => Value::list(map.next_value()?),
+            "vector" => Value::vector(map.next_value()?),
+            "pair" => Value::Pair(Rc::new(map.next_value()?)),
+            "hashmap" => Value::HashMap(map.next_value::<MapEntries>()?.0),
+            "ref" => Value::reference(map.next_value()?),
+            other => {
+                return Err(de::Error::unknown_variant(
+                    other,
+                    &[
+                        "symbol", "keyword", "string", "char", "list", "vector", "pair", "hashmap",
+                        "ref",
+                    ],
+                ))
+            }
+        };
+        if map.next_key::<String>()?.is_some() {
+            return Err(de::Error::custom("tagged value has more than one key"));
+        }
+        Ok(value)
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for Atom {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Value::deserialize(deserializer)? {
+            Value::Atom(atom) => Ok(atom),
+            other => Err(de::Error::custom(format!(
+                "expected an atom, got {}",
+                other.type_name()
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use serde_json::json;
+
+    use crate::{
+        core::default_env,
+        map::Map,
+        reader::read_str,
+        types::{Atom, Closure, Function, Value},
+    };
+
+    #[test]
+    fn test_serialize_nested() {
+        let value = read_str("(foo :bar \"baz\" [1 nil true] {:k false})").unwrap();
+        assert_eq!(
+            serde_json::to_value(&value).unwrap(),
+            json!({"list": [
+                {"symbol": "foo"},
+                {"keyword": "bar"},
+                {"string": "baz"},
+                {"vector": [1, null, true]},
+                {"hashmap": {":k": false}},
+            ]})
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let value = Value::list(vec![
+            Value::Atom(Atom::Symbol("sym".into())),
+            Value::vector(vec![
+                Value::Atom(Atom::String("with \"quotes\"".to_owned())),
+                Value::Atom(Atom::Int(-7)),
+                Value::from(2.5),
+            ]),
+            Value::HashMap(Map::from([
+                (
+                    Atom::String("a".to_owned()),
+                    Value::list(vec![Value::Atom(Atom::Nil)]),
+                ),
+                (Atom::Keyword("a".to_owned()), Value::Atom(Atom::True)),
+                (Atom::Int(1), Value::HashMap(Map::new())),
+            ])),
+            read_str("(1 2 . 3)").unwrap(),
+            Value::Atom(Atom::Char('\n')),
+            Value::reference(Value::vector(vec![Value::Atom(Atom::Nil)])),
+        ]);
+        let json = serde_json::to_string(&value).unwrap();
+        let result: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(result, value);
+    }
+
+    #[test]
+    fn test_deserialize_errors() {
+        assert!(serde_json::from_str::<Value>("[1, 2]").is_err());
+        assert!(serde_json::from_str::<Value>(r#"{"set": []}"#).is_err());
+        assert!(serde_json::from_str::<Value>(r#"{"hashmap": {"(1)": 2}}"#).is_err());
+        assert!(serde_json::from_str::<Atom>(r#"{"list": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_serialize_function_fails() {
+        let function = Value::Function(Function::Closure(Rc::new(Closure {
+            params: vec![],
+            rest: None,
+            is_macro: false,
+            body: Value::Atom(Atom::Nil),
+            env: default_env(),
+            meta: None,
+        })));
+        assert!(serde_json::to_string(&function).is_err());
+    }
+}
@@ -0,0 +1,230 @@
+//! Interned symbol names.
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{self, Debug, Display, Formatter},
+    marker::PhantomData,
+    rc::Rc,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<Rc<str>, u32>,
+    names: Vec<Rc<str>>,
+}
+
+impl Interner {
+    fn intern(&mut self, name: &str) -> u32 {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+        let id = u32::try_from(self.names.len()).expect("too many symbols");
+        let name: Rc<str> = Rc::from(name);
+        self.names.push(name.clone());
+        self.ids.insert(name, id);
+        id
+    }
+}
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::default();
+}
+
+static GENSYM_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// The name of a symbol, interned so that comparing and hashing symbols only
+/// involves an integer.
+///
+/// Interning the same name twice yields equal symbols. The original name can
+/// be recovered with [Symbol::name].
+///
+/// Symbols are interned per thread, so a symbol is only meaningful on the
+/// thread that created it. It is neither [Send] nor [Sync], which keeps it, and
+/// any [Atom](crate::types::Atom) holding it, from being moved to another
+/// thread where its id would name something else:
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<mal::symbol::Symbol>();
+/// ```
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<mal::types::Atom>();
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32, PhantomData<*const ()>);
+
+impl Symbol {
+    /// Intern `name`, returning the symbol for it.
+    pub fn new(name: &str) -> Self {
+        let id = INTERNER.with(|interner| interner.borrow_mut().intern(name));
+        Symbol(id, PhantomData)
+    }
+
+    /// Create a symbol that has never been interned before, named `G__<n>`.
+    ///
+    /// Names that are already taken, for example because they were read from
+    /// source code, are skipped.
+    pub fn gensym() -> Self {
+        INTERNER.with(|interner| {
+            let mut interner = interner.borrow_mut();
+            loop {
+                let n = GENSYM_COUNTER.fetch_add(1, Ordering::Relaxed);
+                let name = format!("G__{n}");
+                if !interner.ids.contains_key(name.as_str()) {
+                    break Symbol(interner.intern(&name), PhantomData);
+                }
+            }
+        })
+    }
+
+    /// The unique id of this symbol.
+    pub fn id(&self) -> u32 {
+        self.0
+    }
+
+    /// The name this symbol was interned from.
+    pub fn name(&self) -> Rc<str> {
+        INTERNER.with(|interner| interner.borrow().names[self.0 as usize].clone())
+    }
+}
+
+/// Split a symbol or keyword name such as `foo/bar` into its namespace and the
+/// name within that namespace.
+///
+/// The split happens at the first `/` that has text on both sides, so `/` on
+/// its own (the division function) and names like `foo/` or `/foo` have no
+/// namespace. A name can itself be `/`, as in `ns//`.
+///
+/// # Examples
+///
+/// ```
+/// use mal::symbol::split_namespace;
+///
+/// assert_eq!(split_namespace("str/join"), (Some("str"), "join"));
+/// assert_eq!(split_namespace("/"), (None, "/"));
+/// ```
+pub fn split_namespace(name: &str) -> (Option<&str>, &str) {
+    match name.split_once('/') {
+        Some((namespace, local)) if !namespace.is_empty() && !local.is_empty() => {
+            (Some(namespace), local)
+        }
+        _ => (None, name),
+    }
+}
+
+/// Symbols are ordered by name rather than by id, so that the order does not
+/// depend on which symbol happened to be interned first.
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        if self == other {
+            return std::cmp::Ordering::Equal;
+        }
+        self.name().cmp(&other.name())
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::new(name)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(name: String) -> Self {
+        Symbol::new(&name)
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_namespace, Symbol};
+    use crate::{
+        reader::read_str,
+        types::{Atom, Value},
+    };
+
+    #[test]
+    fn test_intern() {
+        let a = Symbol::new("interned");
+        let b = Symbol::new("interned");
+        let c = Symbol::new("other");
+        assert_eq!(a, b);
+        assert_eq!(a.id(), b.id());
+        assert_ne!(a, c);
+        assert_eq!(&*a.name(), "interned");
+        assert_eq!(c.to_string(), "other");
+    }
+
+    #[test]
+    fn test_ordered_by_name() {
+        let b = Symbol::new("ordered-b");
+        let a = Symbol::new("ordered-a");
+        assert!(a < b);
+        assert_eq!(a.cmp(&Symbol::new("ordered-a")), std::cmp::Ordering::Equal);
+    }
+
+    #[test]
+    fn test_split_namespace() {
+        assert_eq!(split_namespace("foo/bar"), (Some("foo"), "bar"));
+        assert_eq!(split_namespace("a.b/c/d"), (Some("a.b"), "c/d"));
+        assert_eq!(split_namespace("ns//"), (Some("ns"), "/"));
+        assert_eq!(split_namespace("/"), (None, "/"));
+        assert_eq!(split_namespace("plain"), (None, "plain"));
+        assert_eq!(split_namespace("foo/"), (None, "foo/"));
+        assert_eq!(split_namespace("/foo"), (None, "/foo"));
+    }
+
+    #[test]
+    fn test_read_namespaced_names() {
+        match read_str("(foo/bar :ns/key /)").unwrap().as_seq().unwrap() {
+            [Value::Atom(Atom::Symbol(sym)), Value::Atom(Atom::Keyword(key)), Value::Atom(Atom::Symbol(div))] =>
+            {
+                assert_eq!(split_namespace(&sym.name()), (Some("foo"), "bar"));
+                assert_eq!(split_namespace(key), (Some("ns"), "key"));
+                assert_eq!(split_namespace(&div.name()), (None, "/"));
+            }
+            other => panic!("unexpected read result {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_gensym_is_fresh() {
+        let taken = Symbol::new("G__0");
+        let a = Symbol::gensym();
+        let b = Symbol::gensym();
+        assert_ne!(a, b);
+        assert_ne!(a, taken);
+        assert_ne!(b, taken);
+    }
+
+    #[test]
+    fn test_reads_intern_to_same_id() {
+        let id = |input| match read_str(input).unwrap() {
+            Value::Atom(Atom::Symbol(sym)) => sym.id(),
+            other => panic!("expected a symbol, got {other:?}"),
+        };
+        assert_eq!(id("my-symbol"), id("  my-symbol ; again"));
+        assert_ne!(id("my-symbol"), id("my-other-symbol"));
+    }
+}
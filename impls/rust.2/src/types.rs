@@ -1,43 +1,319 @@
 //! Definitions of mal data types.
-use std::collections::HashMap;
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    fmt::{self, Debug, Formatter},
+    hash::{Hash, Hasher},
+    ops::Deref,
+    rc::Rc,
+};
+
+use crate::{env::Env, eval::EvalError, map::Map, symbol::Symbol};
 
 /// All supported mal data types.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Value {
     /// A single primitive value such as an integer or a string.
     Atom(Atom),
     /// An immutable list of values.
-    List(Vec<Value>),
+    ///
+    /// The items are shared, so cloning a list is cheap.
+    List(Seq),
     /// A mutable vector of values.
-    Vector(Vec<Value>),
-    /// A hash-map of [atoms](crate::types::Atom) to values.
-    HashMap(HashMap<Atom, Value>),
+    ///
+    /// The items are shared, so cloning a vector is cheap.
+    Vector(Seq),
+    /// A cons cell holding a head and a tail, read from dotted pair syntax such
+    /// as `(a . b)`.
+    ///
+    /// A pair whose tail is another pair forms an improper list, so `(a b . c)`
+    /// is `(a . (b . c))`.
+    Pair(Rc<(Value, Value)>),
+    /// A hash-map of [atoms](crate::types::Atom) to values, kept in insertion
+    /// order.
+    HashMap(Map),
+    /// A callable [function](crate::types::Function).
+    Function(Function),
+    /// A mutable reference to a value, created with `atom` and updated with
+    /// `reset!`.
+    Ref(Rc<RefCell<Value>>),
 }
 
 impl Value {
+    /// Create a list containing `items`.
+    pub fn list(items: Vec<Value>) -> Value {
+        Value::List(Seq::new(items))
+    }
+
+    /// Create a vector containing `items`.
+    pub fn vector(items: Vec<Value>) -> Value {
+        Value::Vector(Seq::new(items))
+    }
+
+    /// Create an improper list of `items` ending in `tail`.
+    ///
+    /// If `tail` is a list the result is a proper list of `items` followed by
+    /// the items of `tail`, as `(a . (b c))` is the same as `(a b c)`.
+    pub fn dotted(items: Vec<Value>, tail: Value) -> Value {
+        match tail {
+            Value::List(rest) => {
+                Value::list(items.into_iter().chain(rest.iter().cloned()).collect())
+            }
+            tail => items
+                .into_iter()
+                .rev()
+                .fold(tail, |tail, head| Value::Pair(Rc::new((head, tail)))),
+        }
+    }
+
+    /// Returns the items of this value if it is a list or a vector.
+    ///
+    /// Lists and vectors are interchangeable wherever a sequence is expected,
+    /// such as `let*` bindings and `fn*` parameters.
+    pub fn as_seq(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(items) | Value::Vector(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// The metadata attached to this value, or nil if there is none.
+    pub fn meta(&self) -> Value {
+        let meta = match self {
+            Value::List(seq) | Value::Vector(seq) => &seq.meta,
+            Value::HashMap(map) => &map.meta,
+            Value::Function(Function::Native { meta, .. }) => meta,
+            Value::Function(Function::Closure(closure)) => &closure.meta,
+            _ => &None,
+        };
+        meta.as_deref().cloned().unwrap_or(Value::Atom(Atom::Nil))
+    }
+
+    /// A copy of this value with `meta` attached in place of any existing
+    /// metadata.
+    ///
+    /// Only collections and functions can carry metadata. The copy shares its
+    /// contents with the original, which is left unchanged.
+    pub fn with_meta(&self, meta: Value) -> Result<Value, EvalError> {
+        let meta = Some(Rc::new(meta));
+        Ok(match self {
+            Value::List(seq) => Value::List(Seq {
+                meta,
+                ..seq.clone()
+            }),
+            Value::Vector(seq) => Value::Vector(Seq {
+                meta,
+                ..seq.clone()
+            }),
+            Value::HashMap(map) => Value::HashMap(map.clone().with_meta(meta)),
+            Value::Function(Function::Native { name, func, .. }) => {
+                Value::Function(Function::Native {
+                    name,
+                    func: *func,
+                    meta,
+                })
+            }
+            Value::Function(Function::Closure(closure)) => {
+                Value::Function(Function::Closure(Rc::new(Closure {
+                    meta,
+                    ..closure.copy()
+                })))
+            }
+            other => return Err(type_mismatch("collection or function", other)),
+        })
+    }
+
+    /// Create a reference holding `value`.
+    pub fn reference(value: Value) -> Value {
+        Value::Ref(Rc::new(RefCell::new(value)))
+    }
+
     pub(crate) fn type_name(&self) -> String {
         match self {
-            Value::Atom(_) => "atom",
+            Value::Atom(atom) => atom.type_name(),
             Value::List(_) => "list",
             Value::Vector(_) => "vector",
+            Value::Pair(_) => "pair",
             Value::HashMap(_) => "hashmap",
+            Value::Function(_) => "function",
+            Value::Ref(_) => "ref",
         }
         .to_string()
     }
 }
 
+impl From<i64> for Value {
+    fn from(int: i64) -> Self {
+        Value::Atom(Atom::Int(int))
+    }
+}
+
+impl From<f64> for Value {
+    fn from(float: f64) -> Self {
+        Value::Atom(Atom::Float(Float(float)))
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Atom(if value { Atom::True } else { Atom::False })
+    }
+}
+
+impl From<String> for Value {
+    fn from(string: String) -> Self {
+        Value::Atom(Atom::String(string))
+    }
+}
+
+impl From<&str> for Value {
+    fn from(string: &str) -> Self {
+        Value::Atom(Atom::String(string.to_owned()))
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    /// Creates a list. Use [Value::vector] to create a vector.
+    fn from(items: Vec<Value>) -> Self {
+        Value::list(items)
+    }
+}
+
+fn type_mismatch(expected: &'static str, value: &Value) -> EvalError {
+    EvalError::TypeMismatch {
+        expected,
+        got: value.type_name(),
+    }
+}
+
+impl TryFrom<Value> for i64 {
+    type Error = EvalError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Atom(Atom::Int(int)) => Ok(int),
+            other => Err(type_mismatch("int", &other)),
+        }
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = EvalError;
+
+    /// Integers are converted to floats, possibly losing precision.
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Atom(Atom::Float(Float(float))) => Ok(float),
+            Value::Atom(Atom::Int(int)) => Ok(int as f64),
+            other => Err(type_mismatch("number", &other)),
+        }
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = EvalError;
+
+    /// Only `true` and `false` convert to a bool. Use [is_truthy] to test the
+    /// truthiness of an arbitrary value.
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Atom(Atom::True) => Ok(true),
+            Value::Atom(Atom::False) => Ok(false),
+            other => Err(type_mismatch("bool", &other)),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = EvalError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Atom(Atom::String(string)) => Ok(string),
+            other => Err(type_mismatch("string", &other)),
+        }
+    }
+}
+
+/// Returns true if `value` is considered true in a boolean context.
+///
+/// Only `nil` and `false` are falsey. Everything else, including `0`, the empty
+/// string, and the empty list, is truthy.
+pub fn is_truthy(value: &Value) -> bool {
+    !matches!(value, Value::Atom(Atom::Nil | Atom::False))
+}
+
+/// The items of a list or vector, along with any metadata attached to it.
+///
+/// A `Seq` dereferences to a slice of its items. The items are shared between
+/// clones, and are never modified once created. Metadata is ignored when
+/// comparing sequences.
+#[derive(Clone, Default)]
+pub struct Seq {
+    items: Rc<Vec<Value>>,
+    meta: Option<Rc<Value>>,
+}
+
+impl Seq {
+    /// Create a sequence of `items` with no metadata.
+    pub fn new(items: Vec<Value>) -> Self {
+        Self {
+            items: Rc::new(items),
+            meta: None,
+        }
+    }
+
+    /// Create a sequence of `items` with the same metadata as this one.
+    pub(crate) fn with_items(&self, items: Vec<Value>) -> Self {
+        Self {
+            items: Rc::new(items),
+            meta: self.meta.clone(),
+        }
+    }
+}
+
+impl Deref for Seq {
+    type Target = [Value];
+
+    fn deref(&self) -> &[Value] {
+        &self.items
+    }
+}
+
+impl Debug for Seq {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl PartialEq for Seq {
+    fn eq(&self, other: &Self) -> bool {
+        self.items == other.items
+    }
+}
+
+impl Eq for Seq {}
+
 /// All supported mal atom types.
-#[derive(Debug, PartialEq, Eq, Hash)]
+///
+/// Atoms have a total order: atoms of the same kind compare by value, and
+/// atoms of different kinds by the order of the variants below. The order
+/// across kinds is arbitrary but stable.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Atom {
     /// A named data object.
-    Symbol(String),
+    Symbol(Symbol),
     /// A string value specified with a leading colon `:` instead of surrounding
     /// quotation marks `"`. Commonly used as hash-map keys.
     Keyword(String),
     /// A UTF-8 encoded string of characters.
     String(String),
-    /// Any 32-bit integer value.
-    Int(i32),
+    /// A single character, written as `\c` or by name as in `\newline`.
+    Char(char),
+    /// Any 64-bit integer value.
+    Int(i64),
+    /// A 64-bit floating point value.
+    Float(Float),
     /// The "nothing" atom, used to indicate the absense of a value.
     Nil,
     /// The "true" atom, used to indicate positivity.
@@ -45,3 +321,271 @@ pub enum Atom {
     /// The "false" atom, used to indicate negativity.
     False,
 }
+
+impl Atom {
+    pub(crate) fn type_name(&self) -> &'static str {
+        match self {
+            Atom::Symbol(_) => "symbol",
+            Atom::Keyword(_) => "keyword",
+            Atom::String(_) => "string",
+            Atom::Char(_) => "char",
+            Atom::Int(_) => "int",
+            Atom::Float(_) => "float",
+            Atom::Nil => "nil",
+            Atom::True | Atom::False => "bool",
+        }
+    }
+}
+
+/// A 64-bit floating point number.
+///
+/// Floats compare and hash by their bit pattern so that they can be used as
+/// hash-map keys. This means `NaN` equals itself, but `0.0` and `-0.0` differ.
+/// They are ordered by [f64::total_cmp], which agrees with this equality.
+#[derive(Debug, Clone, Copy)]
+pub struct Float(pub f64);
+
+impl PartialEq for Float {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for Float {}
+
+impl Ord for Float {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl PartialOrd for Float {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Hash for Float {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+/// The signature of a function implemented in Rust.
+///
+/// Native functions receive their already-evaluated arguments along with the
+/// environment they were called from.
+pub type NativeFn = fn(&[Value], &Env) -> Result<Value, EvalError>;
+
+/// A function that can be called from mal code.
+#[derive(Clone)]
+pub enum Function {
+    /// A function implemented in Rust, such as those in [core](crate::core).
+    Native {
+        name: &'static str,
+        func: NativeFn,
+        meta: Option<Rc<Value>>,
+    },
+    /// A function defined in mal with `fn*`.
+    Closure(Rc<Closure>),
+}
+
+impl Debug for Function {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Function::Native { name, .. } => write!(f, "Native({name})"),
+            Function::Closure(closure) => write!(f, "Closure({:?})", closure.params),
+        }
+    }
+}
+
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Function::Native { name: a, .. }, Function::Native { name: b, .. }) => a == b,
+            (Function::Closure(a), Function::Closure(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Function {}
+
+/// A user-defined function along with the environment it was created in.
+pub struct Closure {
+    /// Names of the required parameters.
+    pub(crate) params: Vec<Symbol>,
+    /// Name of the parameter following `&`, if any, which collects the
+    /// remaining arguments into a list.
+    pub(crate) rest: Option<Symbol>,
+    /// The expression evaluated when the function is called.
+    pub(crate) body: Value,
+    /// The environment the function closes over.
+    pub(crate) env: Env,
+    /// Whether this function was defined with `defmacro!`, in which case it is
+    /// called with its arguments unevaluated and its result is evaluated in
+    /// place of the call.
+    pub(crate) is_macro: bool,
+    /// The metadata attached with `with-meta`, if any.
+    pub(crate) meta: Option<Rc<Value>>,
+}
+
+impl Closure {
+    /// A copy of this closure, sharing its body and environment.
+    pub(crate) fn copy(&self) -> Closure {
+        Closure {
+            params: self.params.clone(),
+            rest: self.rest,
+            body: self.body.clone(),
+            env: self.env.clone(),
+            is_macro: self.is_macro,
+            meta: self.meta.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::{Atom, Float, Value};
+    use crate::{eval::EvalError, reader::read_str};
+
+    #[test]
+    fn test_int_ordering() {
+        assert!(Atom::Int(-5) < Atom::Int(3));
+        assert!(Atom::Int(i64::MAX) > Atom::Int(0));
+        assert_eq!(Atom::Int(7).cmp(&Atom::Int(7)), std::cmp::Ordering::Equal);
+        assert!(Float(-0.0) < Float(0.0));
+        assert!(Float(1.5) < Float(f64::NAN));
+    }
+
+    #[test]
+    fn test_atom_ordering_is_total() {
+        let atoms = || {
+            vec![
+                Atom::False,
+                Atom::Int(2),
+                Atom::String("b".to_owned()),
+                Atom::Nil,
+                Atom::Keyword("a".to_owned()),
+                Atom::Float(Float(1.0)),
+                Atom::Symbol("sym".into()),
+                Atom::Int(-1),
+                Atom::True,
+                Atom::Char('c'),
+                Atom::String("a".to_owned()),
+            ]
+        };
+        let mut sorted = atoms();
+        sorted.sort();
+        let mut reversed = atoms();
+        reversed.reverse();
+        reversed.sort();
+        assert_eq!(sorted, reversed);
+        assert!(sorted.windows(2).all(|pair| pair[0] <= pair[1]));
+        let ints: Vec<_> = sorted
+            .iter()
+            .filter(|atom| matches!(atom, Atom::Int(_)))
+            .collect();
+        assert_eq!(ints, [&Atom::Int(-1), &Atom::Int(2)]);
+    }
+
+    #[test]
+    fn test_from() {
+        assert_eq!(Value::from(42), Value::Atom(Atom::Int(42)));
+        assert_eq!(Value::from(true), Value::Atom(Atom::True));
+        assert_eq!(
+            Value::from("hi"),
+            Value::Atom(Atom::String("hi".to_owned()))
+        );
+        assert_eq!(
+            Value::from(vec![Value::from(1), Value::from(false)]),
+            Value::list(vec![Value::Atom(Atom::Int(1)), Value::Atom(Atom::False)])
+        );
+    }
+
+    #[test]
+    fn test_try_from() {
+        assert_eq!(i64::try_from(Value::from(42)), Ok(42));
+        assert_eq!(f64::try_from(Value::from(1.5)), Ok(1.5));
+        assert_eq!(f64::try_from(Value::from(2)), Ok(2.0));
+        assert_eq!(bool::try_from(Value::from(false)), Ok(false));
+        assert_eq!(String::try_from(Value::from("hi")), Ok("hi".to_owned()));
+    }
+
+    #[test]
+    fn test_try_from_mismatch() {
+        assert_eq!(
+            i64::try_from(Value::from("42")),
+            Err(EvalError::TypeMismatch {
+                expected: "int",
+                got: "string".to_owned()
+            })
+        );
+        assert!(bool::try_from(Value::Atom(Atom::Nil)).is_err());
+        assert!(String::try_from(Value::Atom(Atom::Keyword("a".to_owned()))).is_err());
+    }
+
+    #[test]
+    fn test_as_seq() {
+        let items = [Value::from(1), Value::from(2)];
+        assert_eq!(Value::list(items.to_vec()).as_seq(), Some(&items[..]));
+        assert_eq!(Value::vector(items.to_vec()).as_seq(), Some(&items[..]));
+        assert_eq!(read_str("{:a 1}").unwrap().as_seq(), None);
+        assert_eq!(read_str("(1 . 2)").unwrap().as_seq(), None);
+        assert_eq!(Value::from(1).as_seq(), None);
+    }
+
+    #[test]
+    fn test_dotted() {
+        let (a, b, c) = (Value::from(1), Value::from(2), Value::from(3));
+        assert_eq!(
+            Value::dotted(vec![a.clone(), b.clone()], c.clone()),
+            Value::Pair(Rc::new((
+                a.clone(),
+                Value::Pair(Rc::new((b.clone(), c.clone())))
+            )))
+        );
+        assert_eq!(
+            Value::dotted(vec![a.clone()], Value::list(vec![b.clone(), c.clone()])),
+            Value::list(vec![a, b, c])
+        );
+    }
+
+    #[test]
+    fn test_type_name() {
+        let cases = [
+            ("sym", "symbol"),
+            (":kw", "keyword"),
+            ("\"s\"", "string"),
+            ("\\c", "char"),
+            ("1", "int"),
+            ("1.5", "float"),
+            ("nil", "nil"),
+            ("true", "bool"),
+            ("false", "bool"),
+            ("(1)", "list"),
+            ("[1]", "vector"),
+            ("(1 . 2)", "pair"),
+            ("{}", "hashmap"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(read_str(input).unwrap().type_name(), expected, "{input}");
+        }
+        assert_eq!(Value::reference(Value::from(1)).type_name(), "ref");
+    }
+
+    #[test]
+    fn test_clone_shares_items() {
+        let inner = Value::vector((0..10_000).map(Value::from).collect());
+        let list = Value::list(vec![inner.clone(), inner]);
+        let copy = list.clone();
+
+        match (&list, &copy) {
+            (Value::List(a), Value::List(b)) => assert!(std::ptr::eq(a.as_ptr(), b.as_ptr())),
+            _ => unreachable!(),
+        }
+        assert_eq!(list, copy);
+    }
+}
@@ -0,0 +1,219 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    process::{Command, Stdio},
+};
+
+fn mal(args: &[&str]) -> (i32, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_stepA_mal"))
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+fn mal_stdin(input: &str) -> (i32, String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_stepA_mal"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn test_eval_flag() {
+    let (code, stdout, _) = mal(&["-e", "(+ 1 2)"]);
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "3\n");
+}
+
+#[test]
+fn test_eval_flags_share_env() {
+    let (code, stdout, _) = mal(&["-e", "(def! x 20)", "--eval", "(* x 2)"]);
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "20\n40\n");
+}
+
+#[test]
+fn test_def_warns_when_shadowing_builtin() {
+    let (code, stdout, stderr) = mal(&[
+        "-e",
+        "(def! x 1)",
+        "-e",
+        "(def! x 2)",
+        "-e",
+        "(def! + 5)",
+        "-e",
+        "(def! + 6)",
+        "-e",
+        "+",
+    ]);
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "1\n2\n5\n6\n6\n");
+    assert_eq!(stderr, "warning: redefining builtin '+'\n");
+}
+
+#[test]
+fn test_def_does_not_warn_for_aliases_or_inner_scopes() {
+    let (code, stdout, stderr) = mal(&[
+        "-e",
+        "(def! f +)",
+        "-e",
+        "(def! f 1)",
+        "-e",
+        "((fn* () (def! + 2)))",
+        "-e",
+        "(let* [count 3] (def! count 4))",
+        "-e",
+        "(+ 1 2)",
+    ]);
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "#<function>\n1\n2\n4\n3\n");
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn test_no_warn_shadow_flag() {
+    let (code, stdout, stderr) = mal(&["--no-warn-shadow", "-e", "(def! + 5)"]);
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "5\n");
+    assert_eq!(stderr, "");
+}
+
+const COUNT_DOWN: &str = "(def! f (fn* (n) (if (= n 0) 0 (+ 1 (f (- n 1))))))";
+
+#[test]
+fn test_deep_recursion_is_an_error() {
+    let (code, stdout, stderr) = mal(&["-e", COUNT_DOWN, "-e", "(f 5000)", "-e", "(f 1000000)"]);
+    assert_eq!(code, 1);
+    assert_eq!(stdout, "#<function>\n5000\n");
+    assert_eq!(stderr, "error: recursion limit exceeded\n");
+}
+
+#[test]
+fn test_max_depth_env_var() {
+    let output = Command::new(env!("CARGO_BIN_EXE_stepA_mal"))
+        .args(["-e", COUNT_DOWN, "-e", "(f 50)", "-e", "(f 200)"])
+        .env("MAL_MAX_DEPTH", "100")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "#<function>\n50\n"
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "error: recursion limit exceeded\n"
+    );
+}
+
+#[test]
+fn test_eval_flag_error() {
+    let (code, stdout, stderr) = mal(&["-e", "(undefined)", "-e", "1"]);
+    assert_ne!(code, 0);
+    assert_eq!(stdout, "");
+    assert!(stderr.contains("'undefined' not found"));
+}
+
+#[test]
+fn test_eval_flag_missing_expression() {
+    let (code, _, stderr) = mal(&["-e"]);
+    assert_ne!(code, 0);
+    assert!(stderr.contains("-e requires an expression"));
+}
+
+#[test]
+fn test_color_flag() {
+    let (_, plain, _) = mal(&["-e", "[1 :a]"]);
+    let (_, never, _) = mal(&["--color", "never", "-e", "[1 :a]"]);
+    let (_, always, _) = mal(&["--color", "always", "-e", "[1 :a]"]);
+    assert_eq!(plain, "[1 :a]\n");
+    assert_eq!(never, plain);
+    assert!(always.contains("\x1b["));
+}
+
+#[test]
+fn test_color_flag_invalid_mode() {
+    let (code, _, stderr) = mal(&["--color", "sometimes"]);
+    assert_eq!(code, 2);
+    assert!(stderr.contains("invalid color mode"));
+}
+
+#[test]
+fn test_piped_stdin() {
+    let (code, stdout, stderr) = mal_stdin("(def! x 20)\n(* x 2) ; done\n");
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "20\n40\n");
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn test_piped_stdin_error() {
+    let (code, stdout, stderr) = mal_stdin("1 (undefined) 2");
+    assert_eq!(code, 1);
+    assert_eq!(stdout, "1\n");
+    assert!(stderr.contains("'undefined' not found"));
+
+    let (code, _, stderr) = mal_stdin("(1 2");
+    assert_eq!(code, 1);
+    assert!(stderr.contains("unexpected end of input"));
+}
+
+#[test]
+fn test_println_flushes_before_blocking() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_stepA_mal"))
+        .args(["-e", "(do (println \"ready\") (prn :set) (sleep 10000))"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut lines = vec![String::new(), String::new()];
+    for line in &mut lines {
+        stdout.read_line(line).unwrap();
+    }
+    let still_sleeping = child.try_wait().unwrap().is_none();
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert_eq!(lines, ["ready\n", ":set\n"]);
+    assert!(still_sleeping);
+}
+
+#[test]
+fn test_trace() {
+    let (code, stdout, stderr) = mal(&[
+        "-e",
+        "(list :before)",
+        "-e",
+        "(trace true)",
+        "-e",
+        "(list (+ 1 2) :marker)",
+        "-e",
+        "(trace false)",
+        "-e",
+        "(list :after)",
+    ]);
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "(:before)\nnil\n(3 :marker)\nnil\n(:after)\n");
+    assert_eq!(
+        stderr,
+        "(list (+ 1 2) :marker)\n  list\n  (+ 1 2)\n    +\n    1\n    2\n  :marker\n\
+         (trace false)\n  trace\n  false\n"
+    );
+}
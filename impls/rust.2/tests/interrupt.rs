@@ -0,0 +1,28 @@
+//! Kept in its own test binary because an interrupt stops whichever
+//! evaluation is running in the process, including those of other tests.
+use std::{thread, time::Duration};
+
+use mal::{
+    core::default_env,
+    eval::{interrupt, EvalError},
+    rep, MalError,
+};
+
+#[test]
+fn test_interrupt_stops_a_runaway_loop() {
+    let env = default_env();
+    rep("(def! spin (fn* (n) (spin (+ n 1))))", &env).unwrap();
+
+    let interrupter = thread::spawn(|| {
+        thread::sleep(Duration::from_millis(100));
+        interrupt();
+    });
+    assert_eq!(
+        rep("(try* (spin 0) (catch* e :caught))", &env),
+        Err(MalError::Eval(EvalError::Interrupted))
+    );
+    interrupter.join().unwrap();
+
+    // The interrupt is used up, so the next evaluation runs normally.
+    assert_eq!(rep("(+ 1 2)", &env).unwrap(), "3");
+}
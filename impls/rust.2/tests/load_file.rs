@@ -0,0 +1,40 @@
+use std::{env, fs};
+
+use mal::{core::default_env, rep};
+
+#[test]
+fn test_load_file_resolves_relative_to_script() {
+    let root = env::temp_dir().join("mal_test_load_file_relative");
+    let scripts = root.join("scripts");
+    fs::create_dir_all(&scripts).unwrap();
+    fs::write(
+        scripts.join("main.mal"),
+        "(load-file \"helper.mal\")\n(def! main-data (slurp \"data.txt\"))\n",
+    )
+    .unwrap();
+    fs::write(
+        scripts.join("helper.mal"),
+        "(def! helper-data (slurp \"data.txt\"))\n",
+    )
+    .unwrap();
+    fs::write(scripts.join("data.txt"), "sibling").unwrap();
+
+    let env = default_env();
+    let main = scripts.join("main.mal");
+    let result = rep(&format!("(load-file {:?})", main.to_str().unwrap()), &env);
+    fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!(result.unwrap(), "nil");
+    assert_eq!(rep("helper-data", &env).unwrap(), "\"sibling\"");
+    assert_eq!(rep("main-data", &env).unwrap(), "\"sibling\"");
+    assert_eq!(env.file_dir(), None);
+}
+
+#[test]
+fn test_top_level_paths_use_working_directory() {
+    let env = default_env();
+    assert_eq!(
+        rep("(= (slurp \"Cargo.toml\") (slurp \"./Cargo.toml\"))", &env).unwrap(),
+        "true"
+    );
+}
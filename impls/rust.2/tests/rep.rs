@@ -0,0 +1,80 @@
+use mal::{
+    core::{default_env, CoreNamespace},
+    env::Env,
+    eval::EvalError,
+    reader::ReadError,
+    rep, rep_all,
+    types::Value,
+    MalError,
+};
+
+#[test]
+fn test_rep() {
+    let env = default_env();
+    assert_eq!(
+        rep("(def! inc (fn* (x) (+ x 1)))", &env).unwrap(),
+        "#<function>"
+    );
+    assert_eq!(rep("(inc 41)", &env).unwrap(), "42");
+    assert_eq!(rep("(list \"a\" :b)", &env).unwrap(), "(\"a\" :b)");
+}
+
+#[test]
+fn test_rep_errors() {
+    let env = default_env();
+    assert_eq!(
+        rep("(+ 1", &env),
+        Err(MalError::Read(ReadError::UnexpectedEndOfInput(4)))
+    );
+    assert_eq!(
+        rep("nope", &env),
+        Err(MalError::Eval(EvalError::UnboundSymbol("nope".to_owned())))
+    );
+    assert_eq!(rep("", &env), Err(MalError::Read(ReadError::NoInput)));
+}
+
+#[test]
+fn test_rep_all() {
+    let env = default_env();
+    assert_eq!(
+        rep_all("(def! a 1) (def! b 2)", &env),
+        [Ok("1".to_owned()), Ok("2".to_owned())]
+    );
+    assert_eq!(
+        rep_all("a nope b", &env),
+        [
+            Ok("1".to_owned()),
+            Err(MalError::Eval(EvalError::UnboundSymbol("nope".to_owned())))
+        ]
+    );
+    assert_eq!(
+        rep_all("b (+ 1", &env),
+        [Err(MalError::Read(ReadError::UnexpectedEndOfInput(6)))]
+    );
+    assert_eq!(rep_all("; nothing", &env), []);
+}
+
+fn double(args: &[Value], _env: &Env) -> Result<Value, EvalError> {
+    let n = i64::try_from(args[0].clone())?;
+    Ok(Value::from(n * 2))
+}
+
+#[test]
+fn test_custom_builtins() {
+    let env = CoreNamespace::default().with("double", double).into_env();
+    assert_eq!(rep("(double 21)", &env).unwrap(), "42");
+    assert_eq!(rep("(map double [1 2])", &env).unwrap(), "(2 4)");
+    assert_eq!(rep("(or nil (double 1))", &env).unwrap(), "2");
+
+    let env = default_env();
+    assert!(rep("(double 1)", &env).is_err());
+    env.register("double", double);
+    assert_eq!(rep("(double 1)", &env).unwrap(), "2");
+    assert_eq!(
+        rep("(double :a)", &env),
+        Err(MalError::Eval(EvalError::TypeMismatch {
+            expected: "int",
+            got: "keyword".to_owned()
+        }))
+    );
+}